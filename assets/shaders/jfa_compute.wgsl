@@ -0,0 +1,137 @@
+#import water_effect::dimensions
+
+// Compute version of jfa_init.wgsl and jfa.wgsl. Both entry points store the
+// framebuffer-space position of the closest initial fragment, or (-1, -1).
+
+// Bind group 0 imported from water_effect::dimensions
+
+struct JumpDist {
+    dist: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> jump_dist: JumpDist;
+@group(1) @binding(1)
+var src_buffer: texture_2d<f32>;
+@group(1) @binding(2)
+var src_sampler: sampler;
+@group(1) @binding(3)
+var dst_buffer: texture_storage_2d<rg32float, write>;
+
+// Texcoord of the center of the pixel at `id`, as a fullscreen fragment would see it.
+fn pixel_texcoord(id: vec2<u32>) -> vec2<f32> {
+    return (vec2<f32>(id) + 0.5) * vec2<f32>(dims.inv_width, dims.inv_height);
+}
+
+fn out_of_bounds(id: vec2<u32>) -> bool {
+    return f32(id.x) >= dims.width || f32(id.y) >= dims.height;
+}
+
+// Coverage of a fragment by the seeds of the flood: the land, or the water
+// when flooding the inverted mask for the distance field.
+fn coverage(mask_value: f32) -> f32 {
+#ifdef INVERT_MASK
+    return 1.0 - mask_value;
+#else
+    return mask_value;
+#endif
+}
+
+// Jump flood initialization pass, `src_buffer` is the mask.
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (out_of_bounds(id.xy)) {
+        return;
+    }
+
+    let texcoord = pixel_texcoord(id.xy);
+    let dst_coord = vec2<i32>(id.xy);
+
+    // X- and Y-offsets in framebuffer space.
+    let dx = dims.inv_width;
+    let dy = dims.inv_height;
+
+    var samples: mat3x3<f32>;
+    samples[0][0] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, -dy), 0.0).x);
+    samples[0][1] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, 0.0), 0.0).x);
+    samples[0][2] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, dy), 0.0).x);
+    samples[1][0] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(0.0, -dy), 0.0).x);
+    samples[1][1] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord, 0.0).x);
+    samples[1][2] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(0.0, dy), 0.0).x);
+    samples[2][0] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, -dy), 0.0).x);
+    samples[2][1] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, 0.0), 0.0).x);
+    samples[2][2] = coverage(textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, dy), 0.0).x);
+
+    if (samples[1][1] > 0.99) {
+        textureStore(dst_buffer, dst_coord, vec4<f32>(texcoord, 0.0, 1.0));
+        return;
+    }
+
+    if (samples[1][1] < 0.01) {
+        textureStore(dst_buffer, dst_coord, vec4<f32>(-1.0, -1.0, 0.0, 1.0));
+        return;
+    }
+
+    let sobel_x = samples[0][0] + 2.0 * samples[0][1] + samples[0][2] - samples[2][0] - 2.0 * samples[2][1] - samples[2][2];
+    let sobel_y = samples[0][0] + 2.0 * samples[1][0] + samples[2][0] - samples[0][2] - 2.0 * samples[1][2] - samples[2][2];
+    let dir = -vec2<f32>(sobel_x, sobel_y);
+
+    if (abs(dir.x) < 0.005 && abs(dir.y) < 0.005) {
+        textureStore(dst_buffer, dst_coord, vec4<f32>(texcoord, 0.0, 1.0));
+        return;
+    }
+
+    let offset = normalize(dir) * (1.0 - samples[1][1]) * vec2<f32>(dx, dy);
+    textureStore(dst_buffer, dst_coord, vec4<f32>(texcoord + offset, 0.0, 1.0));
+}
+
+// One jump flood step, `src_buffer` is the output of the previous step.
+@compute @workgroup_size(8, 8, 1)
+fn flood(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (out_of_bounds(id.xy)) {
+        return;
+    }
+
+    let texcoord = pixel_texcoord(id.xy);
+
+    // Scaling factor to convert framebuffer to pixel coordinates.
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+    // Pixel coordinates of this invocation.
+    let pix_coord = texcoord * fb_to_pix;
+
+    // X- and Y-offsets in framebuffer space.
+    let dx = dims.inv_width * f32(jump_dist.dist);
+    let dy = dims.inv_height * f32(jump_dist.dist);
+
+    // Largest finite f32, see jfa.wgsl.
+    let infinity = 0x1.FFFFFp127;
+    var min_dist2: f32 = infinity;
+    var min_dist2_pos: vec2<f32> = vec2<f32>(-1.0, -1.0);
+
+    // Fetch 9 samples in a 3x3 grid, jump_dist pixels apart.
+    var samples: array<vec2<f32>, 9>;
+    samples[0] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, -dy), 0.0).xy;
+    samples[1] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, 0.0), 0.0).xy;
+    samples[2] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(-dx, dy), 0.0).xy;
+    samples[3] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(0.0, -dy), 0.0).xy;
+    samples[4] = textureSampleLevel(src_buffer, src_sampler, texcoord, 0.0).xy;
+    samples[5] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(0.0, dy), 0.0).xy;
+    samples[6] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, -dy), 0.0).xy;
+    samples[7] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, 0.0), 0.0).xy;
+    samples[8] = textureSampleLevel(src_buffer, src_sampler, texcoord + vec2<f32>(dx, dy), 0.0).xy;
+
+    for (var i: i32 = 0; i < 9; i = i + 1) {
+        let fb_sample = samples[i];
+        let valid = fb_sample.x != -1.0;
+
+        let delta = pix_coord - fb_sample * fb_to_pix;
+        let dist2 = dot(delta, delta);
+
+        if (valid && dist2 < min_dist2) {
+            min_dist2 = dist2;
+            min_dist2_pos = fb_sample;
+        }
+    }
+
+    textureStore(dst_buffer, vec2<i32>(id.xy), vec4<f32>(min_dist2_pos, 0.0, 1.0));
+}
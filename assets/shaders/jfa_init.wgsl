@@ -7,6 +7,16 @@ var mask_buffer: texture_2d<f32>;
 @group(1) @binding(1)
 var mask_sampler: sampler;
 
+// Coverage of a fragment by the seeds of the flood: the land, or the water
+// when flooding the inverted mask for the distance field.
+fn coverage(mask_value: f32) -> f32 {
+#ifdef INVERT_MASK
+    return 1.0 - mask_value;
+#else
+    return mask_value;
+#endif
+}
+
 struct FragmentIn {
     @location(0) texcoord: vec2<f32>,
 };
@@ -26,15 +36,15 @@ fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
 
     // Fetch 9 samples in a 3x3 grid, jump_dist pixels apart.
     var samples: mat3x3<f32>;
-    samples[0][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, -dy)).x;
-    samples[0][1] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, 0.0)).x;
-    samples[0][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, dy)).x;
-    samples[1][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, -dy)).x;
-    samples[1][1] = textureSample(mask_buffer, mask_sampler, in.texcoord).x;
-    samples[1][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, dy)).x;
-    samples[2][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, -dy)).x;
-    samples[2][1] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, 0.0)).x;
-    samples[2][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, dy)).x;
+    samples[0][0] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, -dy)).x);
+    samples[0][1] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, 0.0)).x);
+    samples[0][2] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, dy)).x);
+    samples[1][0] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, -dy)).x);
+    samples[1][1] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord).x);
+    samples[1][2] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, dy)).x);
+    samples[2][0] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, -dy)).x);
+    samples[2][1] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, 0.0)).x);
+    samples[2][2] = coverage(textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, dy)).x);
 
     if (samples[1][1] > 0.99) {
         return out_position;
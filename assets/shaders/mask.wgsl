@@ -1,39 +1,38 @@
+#import water_effect::fullscreen
+
 // Mask generation shader.
+//
+// Writes the land coverage of each fragment, in [0, 1], derived from the alpha
+// of the rendered water sprites. Fully covered fragments seed the jump flood.
 
-@group(1) @binding(0)
-var water_texture: texture_2d<f32>;
-@group(1) @binding(1)
-var water_sampler: sampler;
+@group(0) @binding(0)
+var water_sprites: texture_2d<f32>;
+@group(0) @binding(1)
+var water_sprites_sampler: sampler;
 
 struct FragmentIn {
     @location(0) texcoord: vec2<f32>,
 };
 
 @fragment
-fn fragment(
-    #import bevy_sprite::mesh2d_vertex_output
-) -> @location(0) vec4<f32> {
-
-    var input_colour: vec4<f32> = textureSample(water_texture, water_sampler, uv);
-
-    // NOTE: it's a bit dumb, but basically it flips the result from the water_sprites shader
-    // so that the water is the one that will have the SDFs calculated for, instead of being the outline of the mesh,
-    // like in Bevy JFA
-    //
-    // Overall, if i could manage to pass the water_texture to the JFA Init node directly, 
-    // I wouldn't need to do dumb stuff here
-    //
-    // It would also allow me to skip the stencil test that Bevy JFA has (in the original MeshMask node), 
-    // because i know already the fragments that i want the SDFs calculated for
-
-
-    // TODO: temporarily removed !!!!!!
-    // if input_colour.a > 0. {
-    //     input_colour = vec4<f32>(0., 0., 0., 0.);
-    // } else {
-    //     input_colour = vec4<f32>(1., 1., 1., 1.);
-    // }
-
-    var result: vec4<f32> = vec4<f32>(input_colour.r, 0., input_colour.b, 1.);;
-    return result;
+fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+    // The sampler filters, which averages the sprites under each fragment
+    // when the mask is smaller than the rendered sprites.
+    let alpha = clamp(textureSample(water_sprites, water_sprites_sampler, in.texcoord).a, 0.0, 1.0);
+
+#ifdef WATER_FROM_SPRITES
+    // The sprites are the water, so the land is wherever they are not.
+    var coverage = 1.0 - alpha;
+#else
+    // The sprites are the coastline.
+    var coverage = alpha;
+#endif
+
+    // Snap coverage within one 8-bit step of empty or full, so that faint
+    // fringes left by filtering don't turn into coastline.
+    let epsilon = 1.0 / 255.0;
+    coverage = select(coverage, 1.0, coverage > 1.0 - epsilon);
+    coverage = select(coverage, 0.0, coverage < epsilon);
+
+    return vec4<f32>(coverage, 0.0, 0.0, 1.0);
 }
@@ -7,35 +7,154 @@ struct Params {
     distance_from_coast: f32,
     frequency: f32, // https://itscai.us/blog/post/jfa/
     speed: f32,
+    foam_color: vec4<f32>,
+    foam_width: f32,
+    foam_noise_scale: f32,
+    foam_noise_speed: f32,
+    wind_direction: vec2<f32>,
 };
 
 struct Time {
     time_since_startup: f32,
 };
 
+struct RippleImpulse {
+    // Centre in view pixels.
+    position: vec2<f32>,
+    // Radius in view pixels the outermost ring has spread to.
+    front: f32,
+    // Strength left after decaying over the lifetime.
+    amplitude: f32,
+};
+
+// Must match MAX_RIPPLE_IMPULSES in uniforms.rs.
+let MAX_RIPPLE_IMPULSES: u32 = 16u;
+
+struct RippleImpulses {
+    impulses: array<RippleImpulse, MAX_RIPPLE_IMPULSES>,
+    count: u32,
+};
+
 @group(1)  @binding(0)
 var jfa_buffer: texture_2d<f32>;
 @group(1) @binding(1)
 var mask_buffer: texture_2d<f32>;
 @group(1) @binding(2)
 var nearest_sampler: sampler;
+#ifdef DISTANCE_FIELD
+// Jump flood from the water, over the inverted mask.
+@group(1) @binding(3)
+var inverted_jfa_buffer: texture_2d<f32>;
+#endif
 
 @group(2) @binding(0)
 var<uniform> params: Params;
+// Water colour by distance from the coast, normalised to distance_from_coast.
+@group(2) @binding(1)
+var water_gradient: texture_1d<f32>;
+@group(2) @binding(2)
+var gradient_sampler: sampler;
 
 @group(3) @binding(0)
 var<uniform> time: Time;
+@group(3) @binding(1)
+var<uniform> ripple_impulses: RippleImpulses;
 
 struct FragmentIn {
     @location(0) texcoord: vec2<f32>,
 };
 
+struct FragmentOut {
+    @location(0) color: vec4<f32>,
+#ifdef COAST_DIRECTION
+    // Unit direction to the nearest coast, relative to the view with y up.
+    @location(1) coast_direction: vec4<f32>,
+#endif
+#ifdef DISTANCE_FIELD
+    // Signed distance to the coast, positive over water.
+    @location(2) distance_field: vec4<f32>,
+#endif
+};
+
+fn fragment_out(color: vec4<f32>, coast_direction: vec2<f32>, signed_distance: f32) -> FragmentOut {
+    var out: FragmentOut;
+    out.color = color;
+#ifdef COAST_DIRECTION
+    out.coast_direction = vec4<f32>(coast_direction, 0.0, 0.0);
+#endif
+#ifdef DISTANCE_FIELD
+    out.distance_field = vec4<f32>(signed_distance, 0.0, 0.0, 0.0);
+#endif
+    return out;
+}
+
+// Distance in view pixels from `pix_coord` to the seed the flood found,
+// clamped to distance_from_coast, which the flood does not reach beyond.
+fn seed_distance(pix_coord: vec2<f32>, fb_seed_pos: vec2<f32>, fb_to_pix: vec2<f32>) -> f32 {
+    let delta = pix_coord - fb_seed_pos * fb_to_pix;
+    return select(
+        params.distance_from_coast,
+        min(length(delta), params.distance_from_coast),
+        fb_seed_pos.x != -1.0,
+    );
+}
+
+let TAU: f32 = 6.28318530718;
+
+// Pseudo-random value in [0, 1) for each integer cell.
+fn hash(cell: vec2<f32>) -> f32 {
+    var p = fract(cell * vec2<f32>(123.34, 456.21));
+    p = p + dot(p, p + 45.32);
+    return fract(p.x * p.y);
+}
+
+// Value noise in [0, 1], smoothly interpolated between integer cells.
+fn value_noise(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+
+    let a = hash(cell);
+    let b = hash(cell + vec2<f32>(1.0, 0.0));
+    let c = hash(cell + vec2<f32>(0.0, 1.0));
+    let d = hash(cell + vec2<f32>(1.0, 1.0));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// How far the rings of the ripple impulses tint the water at pix_coord. The
+// rings trail the front of each impulse, strongest at the front and fading
+// towards its centre.
+fn impulse_rings(pix_coord: vec2<f32>) -> f32 {
+    var rings = 0.0;
+    let count = min(ripple_impulses.count, MAX_RIPPLE_IMPULSES);
+    for (var i = 0u; i < count; i = i + 1u) {
+        let impulse = ripple_impulses.impulses[i];
+        let d = distance(pix_coord, impulse.position);
+        let envelope = select(
+            0.0,
+            d / impulse.front,
+            impulse.front > 0.0 && d <= impulse.front,
+        );
+        let wave = 0.5 + 0.5 * cos(TAU * params.frequency * (impulse.front - d));
+        rings = max(rings, impulse.amplitude * envelope * wave);
+    }
+    return clamp(rings, 0.0, 1.0);
+}
+
 @fragment
-fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+fn fragment(in: FragmentIn) -> FragmentOut {
+    // The JFA buffer may be smaller than the view. It holds framebuffer-space
+    // positions, so nearest sampling upsamples it, and the distance below is
+    // still computed per view pixel. Filtering would blend unrelated seeds.
     let fb_jfa_pos = textureSample(jfa_buffer, nearest_sampler, in.texcoord).xy;
     let fb_to_pix = vec2<f32>(dims.width, dims.height);
 
-    let mask_value = textureSample(mask_buffer, nearest_sampler, in.texcoord).rgb;//.r;
+    // All samples are taken before any early return, which would make the
+    // control flow non-uniform.
+    let mask_value = textureSample(mask_buffer, nearest_sampler, in.texcoord).r;
+#ifdef DISTANCE_FIELD
+    let fb_inverted_jfa_pos = textureSample(inverted_jfa_buffer, nearest_sampler, in.texcoord).xy;
+#endif
 
     // Fragment position in pixel space.
     let pix_coord = in.texcoord * fb_to_pix;
@@ -45,21 +164,71 @@ fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
     let delta = pix_coord - pix_jfa_pos;
     let mag = sqrt(dot(delta, delta));
 
-    // TODO: temporarily removed !!!!!!
-    
-    // Computed texcoord and stored texcoord are likely to differ even if they
-    // represent the same position due to storage as fp16, so an epsilon is
-    // needed.
-    // if (mask_value < 1.0) {
-    //     if (mask_value > 0.0) {
-    //         return vec4<f32>(params.water_color.rgb, 1.0 - mask_value);  // TODO: this makes no sense right now
-    //     } else {
-    //         let fade = clamp(params.frequency - mag, 0.0, 1.0);          // TODO: this makes no sense right now
-    //         return vec4<f32>(params.ripples_color.rgb, fade);            // TODO: this makes no sense right now
-    //     }
-    // } else {
-    //     return vec4<f32>(0.0, 0.0, 0.0, 0.0);
-    // }
-
-    return vec4<f32>(mask_value, 0.85);
-}
\ No newline at end of file
+    // Fragments the flood never reached are further from the coast than the
+    // jump distances cover, i.e. further than distance_from_coast.
+    let valid = fb_jfa_pos.x != -1.0;
+    let distance = select(
+        1.0,
+        clamp(mag / params.distance_from_coast, 0.0, 1.0),
+        valid && params.distance_from_coast > 0.0,
+    );
+    let fade = 1.0 - distance;
+
+    // Distance to the land minus distance to the water: positive over water
+    // and negative over land, through the partially covered fragments.
+    var signed_distance = 0.0;
+#ifdef DISTANCE_FIELD
+    signed_distance = seed_distance(pix_coord, fb_jfa_pos, fb_to_pix)
+        - seed_distance(pix_coord, fb_inverted_jfa_pos, fb_to_pix);
+#endif
+
+    // Map [0, 1] onto the centres of the first and last texels.
+    let gradient_width = f32(textureDimensions(water_gradient));
+    let gradient_coord = (distance * (gradient_width - 1.0) + 0.5) / gradient_width;
+    let water_color = textureSample(water_gradient, gradient_sampler, gradient_coord);
+
+    // Land: leave whatever is underneath untouched.
+    if (mask_value >= 1.0) {
+        return fragment_out(vec4<f32>(0.0, 0.0, 0.0, 0.0), vec2<f32>(0.0, 0.0), signed_distance);
+    }
+
+    // Direction to the nearest coast, flipped to y up.
+    let to_coast = select(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(-delta.x, delta.y) / mag,
+        valid && mag > 0.0,
+    );
+    // Positive on coasts the wind blows onto, negative on sheltered ones.
+    let exposure = dot(to_coast, params.wind_direction);
+
+    // Bands move towards the coast at `speed` pixels per second. Exposure
+    // only scales the distance term, so neighbouring bands stay in step
+    // however long the effect runs: they are tighter and slower on exposed
+    // coasts, wider and faster on sheltered ones.
+    let phase = params.frequency
+        * ((1.0 + 0.5 * exposure) * mag + time.time_since_startup * params.speed);
+    let calm = 1.0 - clamp(-exposure, 0.0, 1.0);
+    let bands = calm * (0.5 + 0.5 * cos(TAU * phase));
+
+    let ripples = mix(water_color, params.ripples_color, bands * fade);
+    // Land returned above, so splashes stay on the water.
+    let splashed = mix(ripples, params.ripples_color, impulse_rings(pix_coord));
+
+    // Two layers of noise drifting apart move the edge of the foam between
+    // half and one and a half foam_width, with a one pixel soft edge.
+    let noise_coord = pix_coord / max(params.foam_noise_scale, 1.0);
+    let drift = time.time_since_startup * params.foam_noise_speed;
+    let noise = 0.5 * (value_noise(noise_coord + vec2<f32>(drift, 0.0))
+        + value_noise(noise_coord * 1.7 - vec2<f32>(0.0, drift)));
+    let foam_edge = params.foam_width * (0.5 + noise);
+    let foam = select(
+        0.0,
+        1.0 - smoothstep(foam_edge - 1.0, foam_edge, mag),
+        valid && params.foam_width > 0.0,
+    );
+
+    let color = mix(splashed, params.foam_color, foam);
+
+    // Partially covered fragments along the coastline fade into the land.
+    return fragment_out(vec4<f32>(color.rgb, color.a * (1.0 - mask_value)), to_coast, signed_distance);
+}
@@ -0,0 +1,84 @@
+//! Fades the water between a day and a night style every few seconds.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+const TRANSITION_SECONDS: f32 = 3.0;
+
+struct DayNight {
+    day: Handle<RipplesStyle>,
+    night: Handle<RipplesStyle>,
+    ripples_camera: Entity,
+    timer: Timer,
+}
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .add_system(cycle)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut ripples_styles: ResMut<Assets<RipplesStyle>>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::new(700., 400.)),
+                ..Default::default()
+            },
+            transform: Transform::from_rotation(Quat::from_rotation_z(0.2)),
+            ..Default::default()
+        })
+        .insert(WaterSource::default());
+
+    let day = ripples_styles.add(RipplesStyle {
+        water_color: Color::rgb(0.1, 0.5, 0.8),
+        ripples_color: Color::rgb(0.8, 0.95, 1.0),
+        ..Default::default()
+    });
+    let night = ripples_styles.add(RipplesStyle {
+        water_color: Color::rgb(0.02, 0.04, 0.15),
+        ripples_color: Color::rgb(0.3, 0.35, 0.6),
+        distance_from_coast: 50.0,
+        speed: 4.0,
+        ..Default::default()
+    });
+
+    let rig = commands.spawn_water_effect(day.clone());
+    commands.insert_resource(DayNight {
+        day,
+        night,
+        ripples_camera: rig.ripples_camera,
+        timer: Timer::from_seconds(2.0 * TRANSITION_SECONDS, true),
+    });
+}
+
+fn cycle(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut day_night: ResMut<DayNight>,
+    styles: Query<&Handle<RipplesStyle>>,
+) {
+    if !day_night.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let current = match styles.get(day_night.ripples_camera) {
+        Ok(style) => style.clone(),
+        Err(_) => return,
+    };
+    let next = if current == day_night.day {
+        day_night.night.clone()
+    } else {
+        day_night.day.clone()
+    };
+
+    commands
+        .entity(day_night.ripples_camera)
+        .insert(RipplesStyleTransition::new(current, next, TRANSITION_SECONDS));
+}
@@ -0,0 +1,36 @@
+//! Loads the ripples style from `assets/styles/lagoon.ripples.ron` and reloads
+//! it whenever the file changes on disk.
+
+use bevy::asset::AssetServerSettings;
+use bevy::prelude::*;
+
+use water_effect::*;
+
+fn main() {
+    App::new()
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::new(700., 400.)),
+                ..Default::default()
+            },
+            transform: Transform::from_rotation(Quat::from_rotation_z(0.2)),
+            ..Default::default()
+        })
+        .insert(WaterSource::default());
+
+    commands.spawn_water_effect(asset_server.load("styles/lagoon.ripples.ron"));
+}
@@ -0,0 +1,126 @@
+//! Measures the frame time of the water effect with either jump flood backend.
+//!
+//! ```text
+//! cargo run --release --example jfa_benchmark -- [--compute] [--scale 0.5]
+//! ```
+//!
+//! Vsync is disabled, the average frame time is logged after a warm-up and
+//! the app exits once the measurement is done.
+
+use bevy::{
+    app::AppExit,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    window::PresentMode,
+};
+
+use water_effect::*;
+
+const WARM_UP_SECONDS: f64 = 3.0;
+const MEASURE_SECONDS: f64 = 10.0;
+
+fn main() {
+    let mut settings = WaterEffectSettings::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compute" => settings.jfa_backend = JfaBackend::Compute,
+            "--scale" => {
+                settings.jfa_resolution_scale = args
+                    .next()
+                    .and_then(|scale| scale.parse().ok())
+                    .expect("--scale expects a number");
+            }
+            other => panic!("unknown argument {}", other),
+        }
+    }
+
+    App::new()
+        .insert_resource(WindowDescriptor {
+            width: 1920.,
+            height: 1080.,
+            present_mode: PresentMode::Immediate,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .insert_resource(Benchmark {
+            backend: settings.jfa_backend,
+            scale: settings.jfa_resolution_scale,
+            frame_times: Vec::new(),
+        })
+        .add_plugins(DefaultPlugins)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(WaterEffectPlugin::new(settings))
+        .add_startup_system(setup)
+        .add_system(measure)
+        .run();
+}
+
+struct Benchmark {
+    backend: JfaBackend,
+    scale: f32,
+    frame_times: Vec<f64>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    // A scattered archipelago, so that most of the screen is near a coast.
+    for x in -4..=4 {
+        for y in -2..=2 {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::WHITE,
+                        custom_size: Some(Vec2::new(150., 120.)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(x as f32 * 210., y as f32 * 200., 0.0)
+                        .with_rotation(Quat::from_rotation_z((x * y) as f32 * 0.2)),
+                    ..Default::default()
+                })
+                .insert(WaterSource::default());
+        }
+    }
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
+
+fn measure(
+    time: Res<Time>,
+    status: Res<WaterEffectStatus>,
+    diagnostics: Res<Diagnostics>,
+    mut benchmark: ResMut<Benchmark>,
+    mut ready_since: Local<Option<f64>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if *status != WaterEffectStatus::Ready {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    let elapsed = now - *ready_since.get_or_insert(now);
+    if elapsed < WARM_UP_SECONDS {
+        return;
+    }
+
+    if let Some(frame_time) = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.value())
+    {
+        benchmark.frame_times.push(frame_time);
+    }
+
+    if elapsed >= WARM_UP_SECONDS + MEASURE_SECONDS {
+        let frames = benchmark.frame_times.len().max(1) as f64;
+        let average = benchmark.frame_times.iter().sum::<f64>() / frames;
+        info!(
+            "{:?} jump flood at {} resolution: {:.3} ms per frame over {} frames",
+            benchmark.backend,
+            benchmark.scale,
+            average * 1000.0,
+            benchmark.frame_times.len(),
+        );
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,55 @@
+//! Cycles the primary window through a few resolutions, to exercise the
+//! water textures being resized along with their render target.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+const RESOLUTIONS: [(f32, f32); 3] = [(1280., 720.), (800., 600.), (1024., 1024.)];
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .insert_resource(ResizeTimer(Timer::from_seconds(3.0, true)))
+        .add_startup_system(setup)
+        .add_system(cycle_resolution)
+        .run();
+}
+
+struct ResizeTimer(Timer);
+
+fn setup(
+    mut commands: Commands,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::new(500., 300.)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(WaterSource::default());
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
+
+fn cycle_resolution(
+    time: Res<Time>,
+    mut timer: ResMut<ResizeTimer>,
+    mut windows: ResMut<Windows>,
+    mut index: Local<usize>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    *index = (*index + 1) % RESOLUTIONS.len();
+    let (width, height) = RESOLUTIONS[*index];
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_resolution(width, height);
+    }
+}
@@ -0,0 +1,73 @@
+//! Three water sprites rotating over a static background.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .add_system(rotate_sprites)
+        .run();
+}
+
+#[derive(Clone, Debug, Component)]
+struct RotationSpeed(f32);
+
+fn setup(
+    mut commands: Commands,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: Color::PURPLE,
+            custom_size: Some(Vec2::new(400., 900.)),
+            ..Default::default()
+        },
+        transform: Transform::from_xyz(50.0, 0.0, 0.0),
+        ..Default::default()
+    });
+
+    let water_sprites = [
+        (Color::RED, Vec2::new(200., 120.), Vec3::new(-200.0, 0.0, 1.0), 0.08),
+        (Color::GREEN, Vec2::new(130., 220.), Vec3::new(0.0, 0.0, 2.0), 0.06),
+        (Color::BLUE, Vec2::new(320., 130.), Vec3::new(200.0, 0.0, 3.0), 0.04),
+    ];
+
+    for (color, size, translation, speed) in water_sprites {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            })
+            .insert(RotationSpeed(speed))
+            .insert(WaterSource::default());
+    }
+
+    commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: Color::ORANGE,
+            custom_size: Some(Vec2::new(150., 150.)),
+            ..Default::default()
+        },
+        transform: Transform::from_xyz(0.0, -100.0, 4.0),
+        ..Default::default()
+    });
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
+
+fn rotate_sprites(time: Res<Time>, mut query: Query<(&mut Transform, &RotationSpeed)>) {
+    let delta = time.delta_seconds();
+
+    for (mut transform, rot) in query.iter_mut() {
+        transform.rotate_z(rot.0 * delta);
+    }
+}
@@ -0,0 +1,52 @@
+//! Pans and zooms the main camera over a coastline, to exercise the water
+//! cameras following it.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .add_system(move_camera)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    // A row of lagoons, wider than the window.
+    for i in -4..=4 {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE,
+                    custom_size: Some(Vec2::new(300., 500.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(i as f32 * 400.0, 0.0, 0.0)
+                    .with_rotation(Quat::from_rotation_z(i as f32 * 0.4)),
+                ..Default::default()
+            })
+            .insert(WaterSource::default());
+    }
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
+
+fn move_camera(
+    time: Res<Time>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let t = time.seconds_since_startup() as f32;
+
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation.x = 1200.0 * (t * 0.2).sin();
+        transform.translation.y = 100.0 * (t * 0.5).sin();
+        projection.scale = 1.25 + 0.5 * (t * 0.3).sin();
+    }
+}
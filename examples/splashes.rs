@@ -0,0 +1,58 @@
+//! Click on the water to splash it with ripple impulses.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .add_system(splash_on_click)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut ripples_styles: ResMut<Assets<RipplesStyle>>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::new(900., 500.)),
+                ..Default::default()
+            },
+            transform: Transform::from_rotation(Quat::from_rotation_z(0.15)),
+            ..Default::default()
+        })
+        .insert(WaterSource::default());
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
+
+fn splash_on_click(
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    cameras: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut impulses: EventWriter<RippleImpulse>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (window, (camera_transform, projection)) =
+        match (windows.get_primary(), cameras.get_single()) {
+            (Some(window), Ok(camera)) => (window, camera),
+            _ => return,
+        };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // The cursor is in logical pixels from the bottom left corner.
+    let window_size = Vec2::new(window.width(), window.height());
+    let position = camera_transform.translation().truncate()
+        + (cursor - window_size / 2.0) * projection.scale;
+    impulses.send(RippleImpulse::new(position, 0.8, 150.0, 2.0));
+}
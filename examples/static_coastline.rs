@@ -0,0 +1,43 @@
+//! A static bay: overlapping water sprites carve a coastline out of the land.
+
+use bevy::prelude::*;
+
+use water_effect::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::rgb(0.76, 0.70, 0.50)))
+        .add_plugins(DefaultPlugins)
+        .add_plugin(WaterEffectPlugin::default())
+        .add_startup_system(setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    // Open sea along the bottom of the screen, plus a bay reaching inland.
+    let water_sprites = [
+        (Vec2::new(1280., 260.), Vec3::new(0.0, -230.0, 0.0), 0.0),
+        (Vec2::new(420., 360.), Vec3::new(-120.0, -20.0, 1.0), 0.3),
+        (Vec2::new(260., 200.), Vec3::new(60.0, 120.0, 2.0), -0.2),
+    ];
+
+    for (size, translation, angle) in water_sprites {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE,
+                    custom_size: Some(size),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation)
+                    .with_rotation(Quat::from_rotation_z(angle)),
+                ..Default::default()
+            })
+            .insert(WaterSource::default());
+    }
+
+    commands.spawn_water_effect(ripples_styles.add(RipplesStyle::default()));
+}
@@ -1,54 +1,87 @@
 use bevy::prelude::*;
+use bevy::reflect::FromReflect;
 use bevy::render::view::RenderLayers;
 use bevy::render::render_resource::*;
 use bevy::render::render_resource::Extent3d;
 use bevy::render::camera::RenderTarget;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
-use bevy::reflect::TypeUuid;
-use bevy::sprite::Material2d;
-use bevy::sprite::MaterialMesh2dBundle;
-use bevy::render::extract_resource::ExtractResource;
+use bevy::render::Extract;
 use bevy::ecs::query::QueryItem;
 use bevy::render::extract_component::ExtractComponent;
 use bevy::ecs::system::lifetimeless::Read;
-use bevy::render::texture::Volume;
-use bevy::render::texture::TextureFormatPixelInfo;
 
+use crate::rig::WaterEffectRig;
 use crate::ripples_style::RipplesStyle;
-
-#[derive(Clone)]
+use crate::settings::WaterEffectSettings;
+
+/// Images a [`RipplesCamera`] renders the water effect through, on the
+/// camera itself.
+///
+/// Every ripples camera has its own, so that any number of them can render
+/// in the same frame. `resize_water_effect_images` keeps them at the size of
+/// the render target of the main camera.
+#[derive(Clone, Component)]
 pub struct WaterEffectImages {
     pub rendered_water_sprites: Handle<Image>,
     pub rendered_ripples: Handle<Image>,
+    /// Unit direction from each water fragment to the nearest coast, relative
+    /// to the view with y up, or zero over land and open water. Only there
+    /// with [`WaterEffectSettings::coast_direction_output`].
+    ///
+    /// This has format `TextureFormat::Rg16Float` and the size of
+    /// `rendered_ripples`.
+    pub coast_direction: Option<Handle<Image>>,
+    /// Signed distance to the coast in view pixels, positive over water and
+    /// negative over land, clamped to the `distance_from_coast` of the
+    /// ripples style. Only there with
+    /// [`WaterEffectSettings::distance_field_output`].
+    ///
+    /// This has format `TextureFormat::R16Float` and the size of
+    /// `rendered_ripples`, so materials can sample it with the texture
+    /// coordinates of the ripples.
+    pub distance_field: Option<Handle<Image>>,
+    water_sprites_render_layer: u8,
 }
 
 impl WaterEffectImages {
-    const WATER_SPRITES_RENDER_LAYER: u8 = 1;
-    const RENDERED_TEXTURE_RENDER_LAYER: u8 = 2;
+    /// Images of 1x1 pixel, resized to the main camera target before the
+    /// first frame they are rendered in.
+    pub fn new(images: &mut Assets<Image>, settings: &WaterEffectSettings) -> Self {
+        Self::with_size(images, settings, Vec2::ONE)
+    }
 
-    pub fn water_sprites_render_layer() -> RenderLayers {
-        RenderLayers::layer(Self::WATER_SPRITES_RENDER_LAYER)
+    fn with_size(images: &mut Assets<Image>, settings: &WaterEffectSettings, target_size: Vec2) -> Self {
+        let size = Self::image_size(target_size);
+        Self {
+            rendered_water_sprites: images.add(Self::rendered_water_sprites_image(size)),
+            rendered_ripples: images
+                .add(Self::rendered_ripples_image(size, settings.ripples_texture_format)),
+            coast_direction: settings
+                .coast_direction_output
+                .then(|| images.add(Self::coast_direction_image(size))),
+            distance_field: settings
+                .distance_field_output
+                .then(|| images.add(Self::distance_field_image(size))),
+            water_sprites_render_layer: settings.water_sprites_render_layer,
+        }
     }
 
-    pub fn rendered_texture_render_layer() -> RenderLayers {
-        RenderLayers::layer(Self::RENDERED_TEXTURE_RENDER_LAYER)
+    pub fn water_sprites_render_layer(&self) -> RenderLayers {
+        RenderLayers::layer(self.water_sprites_render_layer)
     }
 
-    fn image_size(window: &Window) -> Extent3d {
+    /// Size of the images for a render target of `target_size` logical pixels.
+    fn image_size(target_size: Vec2) -> Extent3d {
         let extra_margin = Vec2::ZERO;
-        let adjusted_size = Vec2::new(
-            window.width() + extra_margin.x,
-            window.height() + extra_margin.y,
-        );
+        let adjusted_size = (target_size + extra_margin).as_uvec2().max(UVec2::ONE);
         Extent3d {
-            width: adjusted_size.as_uvec2().x,
-            height: adjusted_size.as_uvec2().y,
+            width: adjusted_size.x,
+            height: adjusted_size.y,
             depth_or_array_layers: 1,
         }
     }
 
-    fn rendered_water_sprites_image(window: &Window) -> Image {
-        let size = Self::image_size(window);
+    fn rendered_water_sprites_image(size: Extent3d) -> Image {
         let mut image = Image {
             texture_descriptor: TextureDescriptor {
                 label: None,
@@ -64,29 +97,24 @@ impl WaterEffectImages {
             ..Default::default()
         };
         // NOTE: fill image.data with zeroes
-        // image.resize(size);
-
-        // TODO: ideally i just refill with 0s, but this below is for debugging
-        image.data.resize(
-            size.volume() * image.texture_descriptor.format.pixel_size(),
-            100,
-        );
+        image.resize(size);
 
         image
     }
 
-    fn rendered_ripples_image(window: &Window) -> Image {
-        let size = Self::image_size(window);
+    fn rendered_ripples_image(size: Extent3d, format: TextureFormat) -> Image {
         let mut image = Image {
             texture_descriptor: TextureDescriptor {
                 label: None,
                 size,
                 dimension: TextureDimension::D2,
-                format: TextureFormat::Bgra8UnormSrgb,
+                format,
                 mip_level_count: 1,
                 sample_count: 1,
+                // COPY_SRC for headless readback.
                 usage: TextureUsages::TEXTURE_BINDING
                     | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
                     | TextureUsages::RENDER_ATTACHMENT,
             },
             ..Default::default()
@@ -96,28 +124,60 @@ impl WaterEffectImages {
 
         image
     }
+
+    fn coast_direction_image(size: Extent3d) -> Image {
+        Self::render_output_image(size, TextureFormat::Rg16Float)
+    }
+
+    fn distance_field_image(size: Extent3d) -> Image {
+        Self::render_output_image(size, TextureFormat::R16Float)
+    }
+
+    /// Image the ripples pass writes into besides the ripples themselves.
+    fn render_output_image(size: Extent3d, format: TextureFormat) -> Image {
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format,
+                mip_level_count: 1,
+                sample_count: 1,
+                // COPY_SRC for headless readback.
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT,
+            },
+            ..Default::default()
+        };
+        image.resize(size);
+
+        image
+    }
 }
 
 impl FromWorld for WaterEffectImages {
     fn from_world(world: &mut World) -> Self {
-        let (water_sprites_image, ripples_image) = {
-        // let image = {
-            let window = world
-                .resource::<Windows>()
-                .get_primary()
-                .expect("cannot get primary Window in Windows");
-            let rendered_water_sprites_image = Self::rendered_water_sprites_image(window);
-            let rendered_ripples_image = Self::rendered_ripples_image(window);
-            (rendered_water_sprites_image, rendered_ripples_image)
-            // image
-        };
-
+        let settings = world.resource::<WaterEffectSettings>().clone();
+        // Only a first guess, `resize_water_effect_images` follows the
+        // render target of the main camera from then on.
+        let target_size = world
+            .get_resource::<Windows>()
+            .and_then(|windows| windows.get_primary())
+            .map_or(Vec2::ONE, |window| Vec2::new(window.width(), window.height()));
         let mut images = world.resource_mut::<Assets<Image>>();
+        Self::with_size(&mut images, &settings, target_size)
+    }
+}
 
-        Self {
-            rendered_water_sprites: images.add(water_sprites_image),
-            rendered_ripples: images.add(ripples_image),
-        }
+impl ExtractComponent for WaterEffectImages {
+    type Query = Read<WaterEffectImages>;
+
+    type Filter = With<RipplesCamera>;
+
+    fn extract_component(images: QueryItem<Self::Query>) -> Self {
+        images.clone()
     }
 }
 
@@ -138,9 +198,11 @@ pub struct MainCameraBundle {
 
 impl Default for MainCameraBundle {
     fn default() -> Self {
-        let mut camera_bundle = Camera2dBundle::default();
-        camera_bundle.camera_2d = Camera2d {
-            clear_color: ClearColorConfig::None,
+        let camera_bundle = Camera2dBundle {
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..Default::default()
         };
         Self {
             main_camera: MainCamera,
@@ -151,13 +213,15 @@ impl Default for MainCameraBundle {
     }
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component)]
 pub struct MainCamera;
 
 #[derive(Bundle)]
 pub struct RipplesCameraBundle {
     tag: RipplesCamera,
     styles_handle: Handle<RipplesStyle>,
+    images: WaterEffectImages,
     render_layers: RenderLayers,
     // visibility: Visibility,
     // computed_visibility: ComputedVisibility,
@@ -167,26 +231,37 @@ pub struct RipplesCameraBundle {
 
 impl RipplesCameraBundle {
     pub fn new(ripples_styles: &mut Assets<RipplesStyle>, water_effect_images: &WaterEffectImages) -> Self {
+        Self::with_style(ripples_styles.add(RipplesStyle::default()), water_effect_images)
+    }
+
+    /// A ripples camera drawing with `style`, e.g. a loaded style asset.
+    pub fn with_style(style: Handle<RipplesStyle>, water_effect_images: &WaterEffectImages) -> Self {
         let image_handle = water_effect_images.rendered_ripples.clone();
 
-        let color = Color::PINK;
+        // Transparent, so that the land shows through the ripples sprite.
+        let color = Color::NONE;
 
-        let mut camera_bundle = Camera2dBundle::default();
-        camera_bundle.camera_2d = Camera2d {
-            //clear_color: ClearColorConfig::None,
-            clear_color: ClearColorConfig::Custom(color),
-        };
-        camera_bundle.camera = Camera {
-            priority: 1,
-            target: RenderTarget::Image(image_handle),
+        let camera_bundle = Camera2dBundle {
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(color),
+            },
+            camera: Camera {
+                priority: 1,
+                target: RenderTarget::Image(image_handle),
+                ..Default::default()
+            },
+            // Keeps the default z of 2D cameras, at the origin the sprites
+            // and quads in front of z = 0 were clipped.
             ..Default::default()
         };
-        camera_bundle.transform = Transform::from_translation(Vec3::ZERO);
 
         Self {
             tag: RipplesCamera,
-            styles_handle: ripples_styles.add(RipplesStyle::default().into()),
-            render_layers: WaterEffectImages::rendered_texture_render_layer(),
+            styles_handle: style,
+            images: water_effect_images.clone(),
+            // Only the ripples pass draws into the ripples image, the mask
+            // pass reads the water sprites straight from their image.
+            render_layers: RenderLayers::none(),
             // visibility: Visibility::default(),
             // computed_visibility: ComputedVisibility::default(),
             camera_bundle,
@@ -194,7 +269,10 @@ impl RipplesCameraBundle {
     }
 }
 
-#[derive(Component)]
+/// Marks a camera that renders the water effect into the
+/// [`WaterEffectImages::rendered_ripples`] of its own [`WaterEffectImages`].
+#[derive(Clone, Copy, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component)]
 pub struct RipplesCamera;
 
 // TODO: try to see if i can remove this
@@ -236,10 +314,9 @@ impl WaterSpritesCameraBundle {
             target: RenderTarget::Image(image_handle),
             ..Default::default()
         };
-        camera_bundle.transform = Transform::from_translation(Vec3::ZERO);
         Self {
             tag: WaterSpritesCamera,
-            render_layers: WaterEffectImages::water_sprites_render_layer(),
+            render_layers: water_effect_images.water_sprites_render_layer(),
             // visibility: Visibility::default(),
             // computed_visibility: ComputedVisibility::default(),
             camera_bundle,
@@ -247,9 +324,68 @@ impl WaterSpritesCameraBundle {
     }
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component)]
 pub struct WaterSpritesCamera;
 
+/// Marks a sprite, sprite sheet or 2D mesh as part of the water.
+///
+/// [`WaterEffectPlugin`](crate::WaterEffectPlugin) keeps its `RenderLayers`
+/// in sync, so that the water sprites camera sees it. Any `RenderLayers` set
+/// by hand are overwritten, and removed along with the component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[reflect(Component, PartialEq)]
+pub struct WaterSource {
+    /// Whether the main camera keeps drawing it too, on the default layer.
+    pub visible_in_main_view: bool,
+}
+
+impl WaterSource {
+    /// A water source that is drawn by the main camera as well.
+    pub fn visible() -> Self {
+        Self {
+            visible_in_main_view: true,
+        }
+    }
+
+    fn render_layers(&self, settings: &WaterEffectSettings) -> RenderLayers {
+        let layers = RenderLayers::layer(settings.water_sprites_render_layer);
+        if self.visible_in_main_view {
+            layers.with(0)
+        } else {
+            layers
+        }
+    }
+}
+
+/// Puts new or changed [`WaterSource`]s on the water sprites render layer.
+pub(crate) fn add_water_source_render_layers(
+    mut commands: Commands,
+    settings: Res<WaterEffectSettings>,
+    water_sources: Query<(Entity, &WaterSource), Changed<WaterSource>>,
+) {
+    for (entity, water_source) in water_sources.iter() {
+        commands
+            .entity(entity)
+            .insert(water_source.render_layers(&settings));
+    }
+}
+
+/// Moves entities that are no longer a [`WaterSource`] back to the default
+/// render layer.
+pub(crate) fn remove_water_source_render_layers(
+    mut commands: Commands,
+    removed: RemovedComponents<WaterSource>,
+    entities: Query<(), With<RenderLayers>>,
+) {
+    for entity in removed.iter() {
+        // Skips despawned entities.
+        if entities.contains(entity) {
+            commands.entity(entity).remove::<RenderLayers>();
+        }
+    }
+}
+
 // #[derive(Bundle)]
 // pub struct WaterEffectBundle {
 //     water_effect: WaterEffect,
@@ -293,98 +429,6 @@ pub struct WaterSpritesCamera;
 // }
 
 
-#[derive(Default, Bundle)]
-pub struct WaterSpritesToTextureBundle {
-    tag: WaterSpritesToTexture,
-    render_layers: RenderLayers,
-    // ripples_style: Handle<RipplesStyle>,
-    // #[bundle]
-    // sprite_bundle: SpriteBundle,
-    #[bundle]
-    pub material_2d_bundle: MaterialMesh2dBundle<WaterSpritesMaterial>,
-}
-
-impl WaterSpritesToTextureBundle {
-    const Z: f32 = 1.0;
-
-    pub fn new(
-        meshes: &mut Assets<Mesh>, 
-        materials: &mut Assets<WaterSpritesMaterial>, 
-        images: &Assets<Image>, 
-        water_effect_images: &WaterEffectImages,
-        // camera_z: f32,
-        // ripples_styles: &mut Assets<RipplesStyle>,
-    ) -> Self {
-        let water_sprites_material = WaterSpritesMaterial::new(&water_effect_images.rendered_water_sprites);
-
-        let image = images.get(&water_effect_images.rendered_water_sprites).unwrap();
-        let mesh_size = UVec2::new(
-            image.texture_descriptor.size.width,
-            image.texture_descriptor.size.height,
-        );
-        let quad = shape::Quad::new(mesh_size.as_vec2());
-
-        // let translation = Vec3::new(0., 0., -camera_z + Self::Z);
-        let translation = Vec3::new(0., 0., Self::Z);
-
-        Self {
-            tag: WaterSpritesToTexture,
-            render_layers: WaterEffectImages::rendered_texture_render_layer(),
-            // ripples_style: ripples_styles.add(RipplesStyle::default().into()),
-            material_2d_bundle: MaterialMesh2dBundle { 
-                mesh: meshes.add(Mesh::from(quad)).into(), 
-                material: materials.add(water_sprites_material), 
-                transform: Transform::from_translation(translation), 
-                ..Default::default()
-            }
-            // sprite_bundle: SpriteBundle {
-            //     sprite: Sprite {
-            //         custom_size: Some(mesh_size.as_vec2()),
-            //         ..Default::default()
-            //      },
-            //     texture: water_effect_images.rendered_water_sprites.clone(),
-            //     transform: Transform::from_translation(translation),
-            //     ..Default::default()
-            // }
-        }
-    }
-}
-
-#[derive(Debug, Default, Component)]
-pub struct WaterSpritesToTexture;
-
-impl ExtractComponent for WaterSpritesToTexture {
-    type Query = Read<WaterSpritesToTexture>;
-
-    type Filter = ();
-
-    fn extract_component(_: QueryItem<Self::Query>) -> Self {
-        WaterSpritesToTexture
-    }
-}
-
-#[derive(Debug, Clone, TypeUuid, AsBindGroup)]
-#[uuid = "d8f3e2a1-ee4e-425c-90c1-125fb82eac1f"]
-pub struct WaterSpritesMaterial {
-    #[texture(0)]
-    #[sampler(1)]
-    pub image_handle: Handle<Image>,
-}
-
-impl WaterSpritesMaterial {
-    pub fn new(image_handle: &Handle<Image>) -> Self {
-        Self {
-            image_handle: image_handle.clone(),
-        }
-    }
-}
-
-impl Material2d for WaterSpritesMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/water_sprites.wgsl".into()
-    }
-}
-
 #[derive(Default, Bundle)]
 pub struct RipplesTextureBundle {
     tag: RipplesTexture,
@@ -435,19 +479,10 @@ impl RipplesTextureBundle {
     }
 }
 
-#[derive(Debug, Default, Component)]
+#[derive(Clone, Copy, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component)]
 pub struct RipplesTexture;
 
-// impl ExtractComponent for WaterSpritesToTexture {
-//     type Query = Read<WaterSpritesToTexture>;
-
-//     type Filter = ();
-
-//     fn extract_component(_: QueryItem<Self::Query>) -> Self {
-//         WaterSpritesToTexture
-//     }
-// }
-
 // #[derive(Debug, Clone, TypeUuid, AsBindGroup)]
 // #[uuid = "d8f3e2a1-ee4e-425c-90c1-125fb82eac1f"]
 // pub struct RipplesMaterial {
@@ -474,17 +509,224 @@ pub struct RipplesTexture;
 //     }
 // }
 
-#[derive(Default)]
-pub struct ExtractedTime {
-    pub seconds_since_startup: f32,
+type WaterCameraFilter = Or<(With<WaterSpritesCamera>, With<RipplesCamera>)>;
+
+/// Resizes the images of every ripples camera, and the sprites displaying
+/// them, to the render target of the main camera it follows.
+///
+/// Runs before `CameraUpdateSystem`, and marks the projections of the water
+/// cameras rendering into resized images as changed, so that they pick up the
+/// new image size in the same frame. Otherwise their render passes would use
+/// the previous size for one frame, against images of the new one.
+pub(crate) fn resize_water_effect_images(
+    rigs: Query<&WaterEffectRig>,
+    water_effect_images: Query<(Entity, &WaterEffectImages)>,
+    windows: Res<Windows>,
+    main_cameras: Query<(Entity, &Camera), With<MainCamera>>,
+    mut water_cameras: Query<(&Camera, &mut OrthographicProjection), WaterCameraFilter>,
+    mut images: ResMut<Assets<Image>>,
+    mut ripples_sprites: Query<(&mut Sprite, &Handle<Image>), With<RipplesTexture>>,
+) {
+    let followed = FollowedMainCameras::new(&rigs, main_cameras.iter().map(|(entity, _)| entity));
+
+    let mut resized = Vec::new();
+    for (entity, water_effect_images) in water_effect_images.iter() {
+        // Straight from the target, the camera itself is only updated later on.
+        let target_size = match followed
+            .get(entity)
+            .and_then(|main_camera| main_cameras.get(main_camera).ok())
+            .and_then(|(_, camera)| camera.target.get_render_target_info(&windows, &images))
+        {
+            Some(info) => info.physical_size.as_vec2() / info.scale_factor as f32,
+            None => continue,
+        };
+
+        let size = WaterEffectImages::image_size(target_size);
+        let current_size = images
+            .get(&water_effect_images.rendered_water_sprites)
+            .map(|image| image.texture_descriptor.size);
+        if current_size == Some(size) {
+            continue;
+        }
+
+        for handle in [
+            Some(&water_effect_images.rendered_water_sprites),
+            Some(&water_effect_images.rendered_ripples),
+            water_effect_images.coast_direction.as_ref(),
+            water_effect_images.distance_field.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(image) = images.get_mut(handle) {
+                image.resize(size);
+            }
+        }
+        resized.push((water_effect_images.clone(), size));
+    }
+
+    for (water_effect_images, size) in resized {
+        let quad_size = Vec2::new(size.width as f32, size.height as f32);
+        for (mut sprite, texture) in ripples_sprites.iter_mut() {
+            if *texture == water_effect_images.rendered_ripples {
+                sprite.custom_size = Some(quad_size);
+            }
+        }
+
+        let targets = [
+            RenderTarget::Image(water_effect_images.rendered_water_sprites),
+            RenderTarget::Image(water_effect_images.rendered_ripples),
+        ];
+        for (camera, mut projection) in water_cameras.iter_mut() {
+            if targets.contains(&camera.target) {
+                projection.set_changed();
+            }
+        }
+    }
+}
+
+type MainCameraView<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static OrthographicProjection),
+    (With<MainCamera>, Without<RipplesTexture>),
+>;
+type WaterCameraViews<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut OrthographicProjection),
+    (WaterCameraFilter, Without<MainCamera>, Without<RipplesTexture>),
+>;
+type RipplesSprites<'w, 's> = Query<'w, 's, Entity, (With<RipplesTexture>, Without<Camera>)>;
+type LocalTransforms<'w, 's> =
+    Query<'w, 's, (&'static mut Transform, Option<&'static Parent>)>;
+
+/// Pairs the water cameras, the ripples sprites and the images of a view
+/// with the main camera they follow.
+pub(crate) struct FollowedMainCameras {
+    rigs: Vec<WaterEffectRig>,
+    // Followed by everything spawned outside of a rig, when there is no
+    // doubt which main camera that is.
+    lone_main_camera: Option<Entity>,
+}
+
+impl FollowedMainCameras {
+    pub(crate) fn new<'a>(
+        rigs: impl IntoIterator<Item = &'a WaterEffectRig>,
+        main_cameras: impl IntoIterator<Item = Entity>,
+    ) -> Self {
+        let mut main_cameras = main_cameras.into_iter();
+        let first = main_cameras.next();
+        Self {
+            rigs: rigs.into_iter().copied().collect(),
+            lone_main_camera: first.filter(|_| main_cameras.next().is_none()),
+        }
+    }
+
+    /// The main camera of the rig `entity` is part of, or the only main
+    /// camera if it is not part of a rig.
+    pub(crate) fn get(&self, entity: Entity) -> Option<Entity> {
+        self.rigs
+            .iter()
+            .find(|rig| {
+                [rig.water_sprites_camera, rig.ripples_camera, rig.ripples_texture].contains(&entity)
+            })
+            .map_or(self.lone_main_camera, |rig| Some(rig.main_camera))
+    }
 }
 
-impl ExtractResource for ExtractedTime {
-    type Source = Time;
+/// Moves the water cameras along with their main camera, and keeps the sprite
+/// displaying the ripples over its whole view.
+///
+/// Puts the water cameras where the main camera is in the world and copies
+/// its orthographic scale, so that the water sprites line up with the world
+/// while it pans or zooms, whoever the cameras are parented to. Each follows
+/// the main camera of its own [`WaterEffectRig`]. Runs before transforms are
+/// propagated and cameras updated, so everything follows in the same frame;
+/// global transforms are computed from the local ones.
+pub(crate) fn follow_main_camera(
+    rigs: Query<&WaterEffectRig>,
+    main_cameras: MainCameraView,
+    mut water_cameras: WaterCameraViews,
+    ripples_sprites: RipplesSprites,
+    mut transforms: LocalTransforms,
+) {
+    let followed = FollowedMainCameras::new(&rigs, main_cameras.iter().map(|(entity, _)| entity));
+    let main_view = |entity: Entity, transforms: &LocalTransforms| {
+        let (main_camera, main_projection) = main_cameras.get(followed.get(entity)?).ok()?;
+        Some((propagated_transform(main_camera, transforms), main_projection.scale))
+    };
+
+    for (entity, mut projection) in water_cameras.iter_mut() {
+        let (main_global, main_scale) = match main_view(entity, &transforms) {
+            Some(view) => view,
+            None => continue,
+        };
+        // Only written on change, so the cameras aren't updated every frame.
+        if projection.scale != main_scale {
+            projection.scale = main_scale;
+        }
+        let transform = local_transform_at(entity, main_global, &transforms);
+        set_if_changed(entity, transform, &mut transforms);
+    }
 
-    fn extract_resource(time: &Self::Source) -> Self {
-        ExtractedTime {
-            seconds_since_startup: time.seconds_since_startup() as f32,
+    for entity in ripples_sprites.iter() {
+        let (main_global, main_scale) = match main_view(entity, &transforms) {
+            Some(view) => view,
+            None => continue,
+        };
+        // The sprite is as large as the view at a scale of 1.
+        let (_, rotation, translation) = main_global.to_scale_rotation_translation();
+        let anchored = GlobalTransform::from(Transform {
+            translation,
+            rotation,
+            scale: Vec3::new(main_scale, main_scale, 1.0),
+        });
+        let mut transform = local_transform_at(entity, anchored, &transforms);
+        // The sprite keeps its own depth.
+        if let Ok((current, _)) = transforms.get(entity) {
+            transform.translation.z = current.translation.z;
         }
+        set_if_changed(entity, transform, &mut transforms);
     }
 }
+
+/// Global transform of `entity` from the local transforms of its ancestors,
+/// before they are propagated.
+fn propagated_transform(entity: Entity, transforms: &LocalTransforms) -> GlobalTransform {
+    let mut global = GlobalTransform::identity();
+    let mut next = Some(entity);
+    while let Some((transform, parent)) = next.and_then(|entity| transforms.get(entity).ok()) {
+        global = GlobalTransform::from(*transform) * global;
+        next = parent.map(Parent::get);
+    }
+    global
+}
+
+/// Local transform that puts `entity` at `global` under its parent.
+fn local_transform_at(entity: Entity, global: GlobalTransform, transforms: &LocalTransforms) -> Transform {
+    let parent_global = match transforms.get(entity) {
+        Ok((_, Some(parent))) => propagated_transform(parent.get(), transforms),
+        _ => GlobalTransform::identity(),
+    };
+    GlobalTransform::from(parent_global.affine().inverse() * global.affine()).compute_transform()
+}
+
+fn set_if_changed(entity: Entity, transform: Transform, transforms: &mut LocalTransforms) {
+    if let Ok((mut current, _)) = transforms.get_mut(entity) {
+        if *current != transform {
+            *current = transform;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ExtractedTime {
+    pub seconds_since_startup: f32,
+}
+
+pub(crate) fn extract_time(mut commands: Commands, time: Extract<Res<Time>>) {
+    commands.insert_resource(ExtractedTime {
+        seconds_since_startup: time.seconds_since_startup() as f32,
+    });
+}
@@ -0,0 +1,39 @@
+use bevy::render::render_resource::{CachedPipelineState, PipelineCacheError};
+
+/// Logs detailed per-frame output, only when the `verbose-diagnostics` feature
+/// is enabled.
+///
+/// Logs at info level, so that the output shows up with Bevy's default log
+/// filter once the feature is turned on.
+macro_rules! verbose {
+    ($($arg:tt)+) => {
+        if cfg!(feature = "verbose-diagnostics") {
+            bevy::log::info!($($arg)+);
+        }
+    };
+}
+
+pub(crate) use verbose;
+
+/// Coarse state of a cached pipeline, as far as the water effect cares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PipelineReadiness {
+    Compiling,
+    Ready,
+    Failed,
+}
+
+impl From<&CachedPipelineState> for PipelineReadiness {
+    fn from(state: &CachedPipelineState) -> Self {
+        match state {
+            CachedPipelineState::Queued => PipelineReadiness::Compiling,
+            CachedPipelineState::Ok(_) => PipelineReadiness::Ready,
+            // Missing shaders are retried by the pipeline cache every frame.
+            CachedPipelineState::Err(
+                PipelineCacheError::ShaderNotLoaded(_)
+                | PipelineCacheError::ShaderImportNotYetAvailable,
+            ) => PipelineReadiness::Compiling,
+            CachedPipelineState::Err(_) => PipelineReadiness::Failed,
+        }
+    }
+}
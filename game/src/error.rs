@@ -0,0 +1,197 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    asset::{HandleId, LoadState},
+    prelude::*,
+    render::{
+        render_graph::RenderGraphError,
+        render_resource::CachedPipelineState,
+    },
+    utils::HashSet,
+};
+
+use crate::diagnostics::{verbose, PipelineReadiness};
+use crate::ripples_style::RipplesStyle;
+
+/// Something that prevents the water effect from rendering.
+///
+/// The render graph nodes never panic on these: they skip the frame instead,
+/// log the error once for as long as it persists, and send it as an event to
+/// the main world so the game can react, e.g. by showing a fallback.
+///
+/// Non-exhaustive, as some variants only exist with some features enabled.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WaterEffectError {
+    /// A ripples camera points to a `RipplesStyle` that failed to load, or was
+    /// never added. Styles that are still loading skip the frame silently.
+    MissingStyle(HandleId),
+    /// The view being rendered has no extracted `RipplesCamera` with a style.
+    MissingCamera(Entity),
+    /// One of the water effect pipelines failed to compile.
+    PipelineCompile {
+        pipeline: &'static str,
+        message: String,
+    },
+    /// The water effect render graph could not be built or attached to the
+    /// 2D render graph.
+    GraphWiring(String),
+    /// A [`HeadlessWaterEffect`](crate::HeadlessWaterEffect) run could not
+    /// render or read back the water effect.
+    #[cfg(feature = "headless")]
+    Headless(String),
+}
+
+impl WaterEffectError {
+    /// Returns the error for a pipeline that failed for good.
+    ///
+    /// Shaders that are still loading also show up as errors in the pipeline
+    /// cache, but those are retried every frame, so they are not reported.
+    pub(crate) fn from_pipeline_state(
+        pipeline: &'static str,
+        state: &CachedPipelineState,
+    ) -> Option<WaterEffectError> {
+        match state {
+            CachedPipelineState::Err(err)
+                if PipelineReadiness::from(state) == PipelineReadiness::Failed =>
+            {
+                Some(WaterEffectError::PipelineCompile {
+                    pipeline,
+                    message: err.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WaterEffectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaterEffectError::MissingStyle(id) => {
+                write!(f, "ripples style {:?} is not loaded", id)
+            }
+            WaterEffectError::MissingCamera(entity) => {
+                write!(f, "view {:?} has no ripples camera with a style", entity)
+            }
+            WaterEffectError::PipelineCompile { pipeline, message } => {
+                write!(f, "{} pipeline failed to compile: {}", pipeline, message)
+            }
+            WaterEffectError::GraphWiring(message) => {
+                write!(f, "could not wire the water effect render graph: {}", message)
+            }
+            #[cfg(feature = "headless")]
+            WaterEffectError::Headless(message) => {
+                write!(f, "headless water effect run failed: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WaterEffectError {}
+
+impl From<RenderGraphError> for WaterEffectError {
+    fn from(err: RenderGraphError) -> Self {
+        WaterEffectError::GraphWiring(err.to_string())
+    }
+}
+
+/// Collects errors raised in the render world, to be sent as
+/// [`WaterEffectError`] events in the main world.
+///
+/// The same instance is inserted in both worlds.
+#[derive(Clone, Default)]
+pub(crate) struct ErrorReporter {
+    queue: Arc<Mutex<ErrorQueue>>,
+}
+
+#[derive(Default)]
+struct ErrorQueue {
+    pending: Vec<WaterEffectError>,
+    /// Errors reported during the previous frame.
+    previous: HashSet<WaterEffectError>,
+    /// Errors reported during the current frame.
+    current: HashSet<WaterEffectError>,
+}
+
+impl ErrorReporter {
+    /// Logs and queues `error`, unless it was already reported this frame or
+    /// the frame before.
+    ///
+    /// An error that is reported every frame is only logged once. Once it is
+    /// not reported for a whole frame, it is forgotten, and logged again if it
+    /// comes back.
+    pub fn report(&self, error: WaterEffectError) {
+        let mut queue = self.queue.lock().unwrap();
+        let persists = queue.previous.contains(&error);
+        if !queue.current.insert(error.clone()) || persists {
+            return;
+        }
+
+        match error {
+            WaterEffectError::MissingStyle(_) | WaterEffectError::MissingCamera(_) => {
+                warn!("{}, skipping the water effect", error)
+            }
+            _ => error!("{}, skipping the water effect", error),
+        }
+        queue.pending.push(error);
+    }
+
+    /// Reports a `style` without prepared params, unless it is still loading.
+    pub fn report_missing_style(&self, asset_server: &AssetServer, style: &Handle<RipplesStyle>) {
+        if asset_server.get_load_state(style) == LoadState::Loading {
+            verbose!("ripples style {:?} is still loading", style.id);
+            return;
+        }
+        self.report(WaterEffectError::MissingStyle(style.id));
+    }
+
+    /// Forgets the errors that were not reported again during the last frame.
+    fn next_frame(&self) {
+        let queue = &mut *self.queue.lock().unwrap();
+        queue.previous = std::mem::take(&mut queue.current);
+    }
+
+    fn drain(&self) -> Vec<WaterEffectError> {
+        std::mem::take(&mut self.queue.lock().unwrap().pending)
+    }
+}
+
+pub(crate) fn send_water_effect_errors(
+    reporter: Res<ErrorReporter>,
+    mut events: EventWriter<WaterEffectError>,
+) {
+    events.send_batch(reporter.drain().into_iter());
+}
+
+/// Starts a new frame of error reports, before the render world runs.
+pub(crate) fn next_water_effect_error_frame(reporter: Res<ErrorReporter>) {
+    reporter.next_frame();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_logged_again_once_they_cleared() {
+        let reporter = ErrorReporter::default();
+        let error = WaterEffectError::MissingCamera(Entity::from_raw(1));
+
+        reporter.report(error.clone());
+        reporter.report(error.clone());
+        assert_eq!(reporter.drain(), vec![error.clone()]);
+
+        // Still there on the next frame.
+        reporter.next_frame();
+        reporter.report(error.clone());
+        assert!(reporter.drain().is_empty());
+
+        // Gone for a frame, then back.
+        reporter.next_frame();
+        reporter.next_frame();
+        reporter.report(error.clone());
+        assert_eq!(reporter.drain(), vec![error]);
+    }
+}
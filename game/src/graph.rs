@@ -2,17 +2,18 @@ use bevy::{
     prelude::*,
     render::{
         render_graph::{
-            Node, NodeRunError, RenderGraph, RenderGraphContext, RenderGraphError, SlotInfo,
-            SlotType,
+            Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType,
         },
-        render_resource::TextureFormat,
         renderer::RenderContext,
-        texture::BevyDefault,
     },
 };
-use bevy::render::camera::ExtractedCamera;
 
 use crate::RipplesCamera;
+use crate::diagnostics::verbose;
+use crate::error::WaterEffectError;
+use crate::resources::ViewWaterEffectResources;
+use crate::jfa_compute::{JfaComputeNode, JfaInitComputeNode};
+use crate::settings::{JfaBackend, WaterEffectSettings};
 use crate::{
     jfa::JfaNode, jfa_init::JfaInitNode, mask::WaterMaskNode, ripples::RipplesNode,
 };
@@ -33,7 +34,7 @@ pub(crate) mod water_effect {
 }
 
 pub struct WaterEffectDriverNode {
-    camera_query: QueryState<&'static ExtractedCamera, With<RipplesCamera>>,
+    camera_query: QueryState<&'static ViewWaterEffectResources, With<RipplesCamera>>,
 }
 
 impl WaterEffectDriverNode {
@@ -60,16 +61,9 @@ impl Node for WaterEffectDriverNode {
     ) -> Result<(), NodeRunError> {
         let view_entity = graph.get_input_entity(Self::INPUT_VIEW)?;
 
-        dbg!(view_entity);
-
-        match self.camera_query.get_manual(world, view_entity) {
-            Ok(_) => {
-                bevy::log::info!("do run subgraph");
-                graph.run_sub_graph(water_effect::NAME, vec![view_entity.into()])?;
-            },
-            Err(_) => {
-                bevy::log::info!("do not run subgraph");
-            }
+        if self.camera_query.get_manual(world, view_entity).is_ok() {
+            verbose!("running the water effect for {:?}", view_entity);
+            graph.run_sub_graph(water_effect::NAME, vec![view_entity.into()])?;
         }
 
         Ok(())
@@ -83,8 +77,40 @@ impl Node for WaterEffectDriverNode {
     }
 }
 
+/// Builds the water effect sub graph and runs it from the 2D render graph,
+/// after the main pass.
+pub fn add_to_core_2d(render_app: &mut App) -> Result<(), WaterEffectError> {
+    let water_effect_driver_node = WaterEffectDriverNode::new(&mut render_app.world);
+    let water_effect_subgraph = water_effect(render_app)?;
+
+    let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+    let draw_2d_graph = root_graph
+        .get_sub_graph_mut(bevy::core_pipeline::core_2d::graph::NAME)
+        .ok_or_else(|| WaterEffectError::GraphWiring("the 2D render graph is missing".into()))?;
+    let draw_2d_input = draw_2d_graph
+        .input_node()
+        .ok_or_else(|| WaterEffectError::GraphWiring("the 2D render graph has no input".into()))?
+        .id;
+
+    draw_2d_graph.add_sub_graph(water_effect::NAME, water_effect_subgraph);
+    let water_effect_driver =
+        draw_2d_graph.add_node(WaterEffectDriverNode::NAME, water_effect_driver_node);
+    draw_2d_graph.add_slot_edge(
+        draw_2d_input,
+        bevy::core_pipeline::core_2d::graph::input::VIEW_ENTITY,
+        water_effect_driver,
+        WaterEffectDriverNode::INPUT_VIEW,
+    )?;
+    draw_2d_graph.add_node_edge(
+        bevy::core_pipeline::core_2d::graph::node::MAIN_PASS,
+        water_effect_driver,
+    )?;
+
+    Ok(())
+}
+
 /// Builds the render graph for applying the JFA outline.
-pub fn water_effect(render_app: &mut App) -> Result<RenderGraph, RenderGraphError> {
+pub fn water_effect(render_app: &mut App) -> Result<RenderGraph, WaterEffectError> {
     let mut graph = RenderGraph::default();
 
     let input_node_id = graph.set_input(vec![SlotInfo {
@@ -92,25 +118,40 @@ pub fn water_effect(render_app: &mut App) -> Result<RenderGraph, RenderGraphErro
         slot_type: SlotType::Entity,
     }]);
 
-    dbg!(&input_node_id);
-
     // Graph order:
     // 1. Mask
     // 2. JFA Init
     // 3. JFA
     // 4. Ripples
 
-    let mask_node = WaterMaskNode::new(&mut render_app.world);
-    let jfa_init_node = JfaInitNode;
-    let jfa_node = JfaNode::from_world(&mut render_app.world);
-    // TODO: BevyDefault for surface texture format is an anti-pattern;
-    // the target texture format should be queried from the window when
-    // Bevy exposes that functionality.
-    let ripples_node = RipplesNode::new(&mut render_app.world, TextureFormat::bevy_default());
+    let mask_node = WaterMaskNode::from_world(&mut render_app.world);
+    let settings = render_app.world.resource::<WaterEffectSettings>().clone();
+    let ripples_node = RipplesNode::new(&mut render_app.world, settings.ripples_texture_format)?;
 
     graph.add_node(water_effect::node::MASK_PASS, mask_node);
-    graph.add_node(water_effect::node::JFA_INIT_PASS, jfa_init_node);
-    graph.add_node(water_effect::node::JFA_PASS, jfa_node);
+    // Both backends have the same slots.
+    match settings.jfa_backend {
+        JfaBackend::RenderPass => {
+            graph.add_node(
+                water_effect::node::JFA_INIT_PASS,
+                JfaInitNode::from_world(&mut render_app.world),
+            );
+            graph.add_node(
+                water_effect::node::JFA_PASS,
+                JfaNode::from_world(&mut render_app.world),
+            );
+        }
+        JfaBackend::Compute => {
+            graph.add_node(
+                water_effect::node::JFA_INIT_PASS,
+                JfaInitComputeNode::from_world(&mut render_app.world),
+            );
+            graph.add_node(
+                water_effect::node::JFA_PASS,
+                JfaComputeNode::from_world(&mut render_app.world),
+            );
+        }
+    }
     graph.add_node(water_effect::node::RIPPLES_PASS, ripples_node);
 
     // Input -> Mask
@@ -121,6 +162,14 @@ pub fn water_effect(render_app: &mut App) -> Result<RenderGraph, RenderGraphErro
         WaterMaskNode::IN_VIEW,
     )?;
 
+    // Input -> JFA Init
+    graph.add_slot_edge(
+        input_node_id,
+        water_effect::input::VIEW_ENTITY,
+        water_effect::node::JFA_INIT_PASS,
+        JfaInitNode::IN_VIEW,
+    )?;
+
     // Mask -> JFA Init
     graph.add_slot_edge(
         water_effect::node::MASK_PASS,
@@ -171,7 +220,7 @@ pub fn water_effect(render_app: &mut App) -> Result<RenderGraph, RenderGraphErro
     //     RipplesNode::IN_JFA, // actually IN_MASK, but ok
     // )?;
 
-    dbg!(&graph);
+    verbose!("water effect render graph: {:?}", graph);
 
     Ok(graph)
 }
@@ -0,0 +1,539 @@
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bevy::{
+    ecs::schedule::IntoSystemDescriptor,
+    log::LogPlugin,
+    prelude::*,
+    render::{
+        camera::{ExtractedCamera, RenderTarget},
+        render_asset::RenderAssets,
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        settings::{Backends, PowerPreference, WgpuSettings},
+        texture::BevyDefault,
+        RenderApp, RenderStage,
+    },
+    time::TimeSystem,
+    winit::WinitPlugin,
+};
+
+use crate::{
+    components::{RipplesCamera, WaterEffectImages},
+    error::WaterEffectError,
+    plugin::WaterEffectPlugin,
+    resources::ViewWaterEffectResources,
+    rig::WaterEffectCommandsExt,
+    ripples_style::RipplesStyle,
+    settings::WaterEffectSettings,
+    status::WaterEffectStatus,
+};
+
+/// Renders the water effect into an offscreen image, without a window, and
+/// reads the intermediate images back.
+///
+/// Meant for tests: the app runs on the software adapter, and time advances
+/// by [`seconds_per_frame`](Self::seconds_per_frame) every frame once the
+/// pipelines are ready, so the output only depends on the scene and the
+/// shaders.
+#[derive(Clone, Debug)]
+pub struct HeadlessWaterEffect {
+    pub settings: WaterEffectSettings,
+    /// Style of the ripples camera.
+    pub style: RipplesStyle,
+    /// Size of the main camera target, in pixels.
+    pub size: UVec2,
+    /// World position the main camera looks at.
+    pub camera_position: Vec2,
+    /// Orthographic scale of the main camera.
+    pub camera_scale: f32,
+    /// Frames rendered once the pipelines are ready, the last one is read back.
+    pub frames: u32,
+    pub seconds_per_frame: f32,
+    /// Folder the shaders are loaded from.
+    pub asset_folder: String,
+    /// Frames to wait for the shaders to load and the pipelines to compile.
+    pub max_startup_frames: u32,
+}
+
+impl Default for HeadlessWaterEffect {
+    fn default() -> Self {
+        Self {
+            settings: WaterEffectSettings::default(),
+            style: RipplesStyle::default(),
+            size: UVec2::new(256, 256),
+            camera_position: Vec2::ZERO,
+            camera_scale: 1.0,
+            frames: 3,
+            seconds_per_frame: 1.0 / 60.0,
+            asset_folder: "assets".into(),
+            max_startup_frames: 1000,
+        }
+    }
+}
+
+/// Images of the water effect read back from the last frame of a
+/// [`HeadlessWaterEffect`] run.
+#[derive(Clone, Debug)]
+pub struct WaterEffectCapture {
+    /// The `rendered_ripples` image.
+    pub ripples: CapturedImage,
+    /// Output of the mask pass, the land coverage of each fragment.
+    pub mask: CapturedImage,
+    /// Final output of the jump flood.
+    pub jfa: CapturedImage,
+    /// The `coast_direction` image, with
+    /// [`WaterEffectSettings::coast_direction_output`].
+    pub coast_direction: Option<CapturedImage>,
+    /// The `distance_field` image, with
+    /// [`WaterEffectSettings::distance_field_output`].
+    pub distance_field: Option<CapturedImage>,
+}
+
+/// Texture data copied back from the GPU, row by row without padding.
+#[derive(Clone, Debug)]
+pub struct CapturedImage {
+    pub size: UVec2,
+    pub format: TextureFormat,
+    pub data: Vec<u8>,
+}
+
+impl CapturedImage {
+    /// Converts the image to 8 bit RGBA, for saving and comparing.
+    ///
+    /// Jump flood positions and coast directions are mapped from `-1.0..=1.0`
+    /// to `0..=255`, so that invalid positions are black. Signed distances
+    /// are mapped to one grey level per pixel around 128, clamped.
+    ///
+    /// Fails for formats the water effect never captures, which have no
+    /// conversion.
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, WaterEffectError> {
+        let pixels = (self.size.x * self.size.y) as usize;
+        let mut rgba = Vec::with_capacity(pixels * 4);
+        let snorm_to_unorm = |value: f32| ((value.clamp(-1.0, 1.0) * 0.5 + 0.5) * 255.0).round() as u8;
+
+        for pixel in self.data.chunks_exact(self.data.len() / pixels.max(1)) {
+            let [r, g, b, a] = match self.format {
+                TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                    [pixel[0], pixel[1], pixel[2], pixel[3]]
+                }
+                TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                    [pixel[2], pixel[1], pixel[0], pixel[3]]
+                }
+                TextureFormat::R8Unorm => [pixel[0], pixel[0], pixel[0], 255],
+                TextureFormat::Rg16Snorm => {
+                    let x = i16::from_le_bytes([pixel[0], pixel[1]]) as f32 / i16::MAX as f32;
+                    let y = i16::from_le_bytes([pixel[2], pixel[3]]) as f32 / i16::MAX as f32;
+                    [snorm_to_unorm(x), snorm_to_unorm(y), 0, 255]
+                }
+                TextureFormat::Rg16Float => {
+                    let x = f16_to_f32(u16::from_le_bytes([pixel[0], pixel[1]]));
+                    let y = f16_to_f32(u16::from_le_bytes([pixel[2], pixel[3]]));
+                    [snorm_to_unorm(x), snorm_to_unorm(y), 0, 255]
+                }
+                TextureFormat::R16Float => {
+                    let distance = f16_to_f32(u16::from_le_bytes([pixel[0], pixel[1]]));
+                    let grey = (128.0 + distance).round().clamp(0.0, 255.0) as u8;
+                    [grey, grey, grey, 255]
+                }
+                TextureFormat::Rg32Float => {
+                    let x = f32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    let y = f32::from_le_bytes([pixel[4], pixel[5], pixel[6], pixel[7]]);
+                    [snorm_to_unorm(x), snorm_to_unorm(y), 0, 255]
+                }
+                format => {
+                    return Err(WaterEffectError::Headless(format!(
+                        "no RGBA conversion for {:?}",
+                        format
+                    )))
+                }
+            };
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+        Ok(rgba)
+    }
+}
+
+/// Decodes an IEEE 754 half precision float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Render world side of a capture: asked for by the main world before the
+/// last frame, filled in after the render graph ran.
+#[derive(Clone, Default)]
+struct CaptureSlot(Arc<Mutex<CaptureState>>);
+
+#[derive(Default)]
+struct CaptureState {
+    requested: bool,
+    capture: Option<WaterEffectCapture>,
+}
+
+impl HeadlessWaterEffect {
+    /// Information about the software adapter used for headless runs.
+    ///
+    /// Fails when there is no software adapter, or when it cannot create the
+    /// textures these settings need: texture errors panic on a render thread,
+    /// which would hang the run instead of failing it.
+    pub fn software_adapter(&self) -> Result<WgpuAdapterInfo, WaterEffectError> {
+        self.find_software_adapter().map(|(_, info)| info)
+    }
+
+    fn find_software_adapter(&self) -> Result<(Backends, WgpuAdapterInfo), WaterEffectError> {
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or_else(Backends::all);
+        let (backend, adapter) = [Backends::VULKAN, Backends::METAL, Backends::DX12, Backends::GL]
+            .into_iter()
+            .filter(|backend| backends.contains(*backend))
+            .find_map(|backend| {
+                let instance = wgpu::Instance::new(backend);
+                let adapter = futures_lite::future::block_on(instance.request_adapter(
+                    &wgpu::RequestAdapterOptions {
+                        power_preference: PowerPreference::LowPower,
+                        force_fallback_adapter: true,
+                        compatible_surface: None,
+                    },
+                ))?;
+                Some((backend, adapter))
+            })
+            .ok_or_else(|| WaterEffectError::Headless("no software adapter is available".into()))?;
+
+        let info = adapter.get_info();
+        // Bevy enables every feature of the adapter.
+        let adapter_specific = adapter
+            .features()
+            .contains(WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let textures = [
+            (self.settings.jfa_format(), self.settings.jfa_usages()),
+            (
+                self.settings.ripples_texture_format,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            ),
+        ];
+        for (format, usages) in textures {
+            let describe = format.describe();
+            let allowed = if adapter_specific {
+                adapter.get_texture_format_features(format).allowed_usages
+            } else {
+                describe.guaranteed_format_features.allowed_usages
+            };
+            if !adapter.features().contains(describe.required_features) || !allowed.contains(usages) {
+                return Err(WaterEffectError::Headless(format!(
+                    "{} does not support {:?} textures with {:?}",
+                    info.name, format, usages
+                )));
+            }
+        }
+
+        Ok((backend, info))
+    }
+
+    /// Builds the app, spawns the cameras and runs `setup` at startup to
+    /// spawn the scene, then renders until the pipelines are ready and
+    /// [`frames`](Self::frames) more.
+    pub fn run<Params>(
+        &self,
+        setup: impl IntoSystemDescriptor<Params>,
+    ) -> Result<WaterEffectCapture, WaterEffectError> {
+        let (backend, software_adapter) = self.find_software_adapter()?;
+
+        let mut app = App::new();
+        app.insert_resource(WgpuSettings {
+            // Bevy cannot ask for the fallback adapter. Restricting the
+            // backend narrows its choice, and the adapter it picked is
+            // checked once the renderer is initialized.
+            backends: Some(backend),
+            power_preference: PowerPreference::LowPower,
+            ..Default::default()
+        })
+        .insert_resource(bevy::window::WindowSettings {
+            add_primary_window: false,
+            exit_on_all_closed: false,
+            close_when_requested: false,
+        })
+        .insert_resource(bevy::asset::AssetServerSettings {
+            asset_folder: self.asset_folder.clone(),
+            watch_for_changes: false,
+        })
+        .insert_resource(FrozenTime(0.0))
+        .insert_resource(HeadlessTarget {
+            size: self.size,
+            camera_position: self.camera_position,
+            camera_scale: self.camera_scale,
+            style: self.style.clone(),
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            // Tests run several apps in one process, the log subscriber can
+            // only be set once.
+            group.disable::<WinitPlugin>().disable::<LogPlugin>()
+        })
+        .add_plugin(WaterEffectPlugin::new(self.settings.clone()))
+        .add_system_to_stage(CoreStage::First, freeze_time.after(TimeSystem))
+        .add_startup_system(spawn_headless_cameras)
+        .add_startup_system(setup);
+
+        let adapter = app.world.resource::<WgpuAdapterInfo>();
+        if (&adapter.name, adapter.backend) != (&software_adapter.name, software_adapter.backend) {
+            return Err(WaterEffectError::Headless(format!(
+                "rendering on {} ({:?}) instead of the software adapter {} ({:?})",
+                adapter.name, adapter.backend, software_adapter.name, software_adapter.backend
+            )));
+        }
+
+        let slot = CaptureSlot::default();
+        app.sub_app_mut(RenderApp)
+            .insert_resource(slot.clone())
+            .add_system_to_stage(RenderStage::Cleanup, capture_water_effect);
+
+        // Time stays at zero until the pipelines are ready, however many
+        // frames that takes, so that the captured frame is always the same.
+        let mut ready = false;
+        for _ in 0..self.max_startup_frames {
+            app.update();
+            match app.world.resource::<WaterEffectStatus>() {
+                WaterEffectStatus::Ready => {
+                    ready = true;
+                    break;
+                }
+                WaterEffectStatus::Failed(err) => return Err(err.clone()),
+                // Shaders load on the IO task pool.
+                WaterEffectStatus::Compiling => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        if !ready {
+            return Err(WaterEffectError::Headless(format!(
+                "the pipelines were not ready after {} frames",
+                self.max_startup_frames
+            )));
+        }
+
+        for _ in 1..self.frames {
+            self.update(&mut app);
+        }
+        slot.0.lock().unwrap().requested = true;
+        self.update(&mut app);
+
+        let capture = slot.0.lock().unwrap().capture.take();
+        capture.ok_or_else(|| WaterEffectError::Headless("no ripples camera was rendered".into()))
+    }
+
+    fn update(&self, app: &mut App) {
+        app.world.resource_mut::<FrozenTime>().0 += self.seconds_per_frame;
+        app.update();
+    }
+}
+
+/// Time since startup that [`Time`] is set to every frame, so that the output
+/// does not depend on how fast frames render.
+struct FrozenTime(f32);
+
+fn freeze_time(frozen_time: Res<FrozenTime>, mut time: ResMut<Time>) {
+    let instant = time.startup() + Duration::from_secs_f32(frozen_time.0);
+    time.update_with_instant(instant);
+}
+
+struct HeadlessTarget {
+    size: UVec2,
+    camera_position: Vec2,
+    camera_scale: f32,
+    style: RipplesStyle,
+}
+
+/// Spawns the water effect cameras, with the main camera rendering into an
+/// image instead of a window.
+fn spawn_headless_cameras(
+    mut commands: Commands,
+    target: Res<HeadlessTarget>,
+    mut images: ResMut<Assets<Image>>,
+    mut ripples_styles: ResMut<Assets<RipplesStyle>>,
+) {
+    let size = Extent3d {
+        width: target.size.x.max(1),
+        height: target.size.y.max(1),
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("water_effect_headless_target"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..Default::default()
+    };
+    image.resize(size);
+    let target_image = images.add(image);
+
+    let rig = commands.spawn_water_effect(ripples_styles.add(target.style.clone()));
+    let defaults = Camera2dBundle::default();
+    commands
+        .entity(rig.main_camera)
+        .insert(Camera {
+            target: RenderTarget::Image(target_image),
+            ..Default::default()
+        })
+        .insert(Transform::from_translation(
+            target.camera_position.extend(defaults.transform.translation.z),
+        ))
+        .insert(OrthographicProjection {
+            scale: target.camera_scale,
+            ..defaults.projection
+        });
+}
+
+/// Copies the images of the first ripples camera to the CPU, once the render
+/// graph has been submitted.
+fn capture_water_effect(
+    slot: Res<CaptureSlot>,
+    settings: Res<WaterEffectSettings>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    gpu_images: Res<RenderAssets<Image>>,
+    views: Query<(&ExtractedCamera, &ViewWaterEffectResources, &WaterEffectImages), With<RipplesCamera>>,
+) {
+    let mut state = slot.0.lock().unwrap();
+    if !state.requested {
+        return;
+    }
+
+    let (camera, view_res, water_effect_images) = match views.iter().next() {
+        Some(view) => view,
+        None => return,
+    };
+    let ripples = match &camera.target {
+        RenderTarget::Image(handle) => match gpu_images.get(handle) {
+            Some(image) => image,
+            None => return,
+        },
+        RenderTarget::Window(_) => return,
+    };
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("water_effect_capture"),
+    });
+    let readbacks = [
+        (&ripples.texture, ripples.size, ripples.texture_format),
+        (
+            &view_res.mask_output.texture,
+            Vec2::new(view_res.jfa_dimensions.width, view_res.jfa_dimensions.height),
+            TextureFormat::R8Unorm,
+        ),
+        (
+            &view_res.jfa.final_output.texture,
+            Vec2::new(view_res.jfa_dimensions.width, view_res.jfa_dimensions.height),
+            settings.jfa_format(),
+        ),
+    ]
+    .map(|(texture, size, format)| Readback::copy(&device, &mut encoder, texture, size.as_uvec2(), format));
+    let mut copy_optional = |image: Option<&Handle<Image>>| {
+        image.and_then(|handle| gpu_images.get(handle)).map(|image| {
+            let size = image.size.as_uvec2();
+            Readback::copy(&device, &mut encoder, &image.texture, size, image.texture_format)
+        })
+    };
+    let coast_direction = copy_optional(water_effect_images.coast_direction.as_ref());
+    let distance_field = copy_optional(water_effect_images.distance_field.as_ref());
+    queue.submit([encoder.finish()]);
+
+    let [ripples, mask, jfa] = readbacks.map(|readback| readback.read(&device));
+    let coast_direction = coast_direction.map(|readback| readback.read(&device));
+    let distance_field = distance_field.map(|readback| readback.read(&device));
+    state.capture = Some(WaterEffectCapture {
+        ripples,
+        mask,
+        jfa,
+        coast_direction,
+        distance_field,
+    });
+    state.requested = false;
+}
+
+struct Readback {
+    buffer: Buffer,
+    size: UVec2,
+    format: TextureFormat,
+    bytes_per_row: usize,
+    padded_bytes_per_row: usize,
+}
+
+impl Readback {
+    fn copy(
+        device: &RenderDevice,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        size: UVec2,
+        format: TextureFormat,
+    ) -> Self {
+        let bytes_per_row = size.x as usize * format.describe().block_size as usize;
+        let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(bytes_per_row);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("water_effect_capture_buffer"),
+            size: (padded_bytes_per_row * size.y as usize) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row as u32),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            buffer,
+            size,
+            format,
+            bytes_per_row,
+            padded_bytes_per_row,
+        }
+    }
+
+    fn read(self, device: &RenderDevice) -> CapturedImage {
+        let slice = self.buffer.slice(..);
+        device.map_buffer(&slice, MapMode::Read, |result| {
+            result.expect("failed to map the capture buffer")
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let data = slice
+            .get_mapped_range()
+            .chunks_exact(self.padded_bytes_per_row)
+            .flat_map(|row| &row[..self.bytes_per_row])
+            .copied()
+            .collect();
+        self.buffer.unmap();
+
+        CapturedImage {
+            size: self.size,
+            format: self.format,
+            data,
+        }
+    }
+}
@@ -8,38 +8,18 @@ use bevy::{
         renderer::RenderContext,
     },
 };
-use bevy::render::render_resource::TextureFormat;
-use bevy::render::texture::BevyDefault;
-use bevy::render::render_resource::PrimitiveState;
 
 use crate::{
-    resources::WaterEffectResources, ripples_style::RipplesStyle,
-    FULLSCREEN_PRIMITIVE_STATE, JFA_TEXTURE_FORMAT,
+    diagnostics::verbose,
+    error::{ErrorReporter, WaterEffectError},
+    jfa_init::JfaInitPipeline,
+    mask::WaterMaskPipeline,
+    resources::{JfaFlood, ViewWaterEffectResources, WaterEffectResources}, ripples_style::RipplesStyle,
+    settings::WaterEffectSettings, status::PipelineTracker,
+    style_transition::{view_ripples_params, BlendedRipplesParams}, FULLSCREEN_PRIMITIVE_STATE,
 };
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, ShaderType)]
-pub struct JumpDist {
-    pub dist: u32,
-}
-
-#[derive(Copy, Clone, Debug, PartialEq, ShaderType)]
-pub struct Dimensions {
-    width: f32,
-    height: f32,
-    inv_width: f32,
-    inv_height: f32,
-}
-
-impl Dimensions {
-    pub fn new(width: u32, height: u32) -> Dimensions {
-        Dimensions {
-            width: width as f32,
-            height: height as f32,
-            inv_width: 1.0 / width as f32,
-            inv_height: 1.0 / height as f32,
-        }
-    }
-}
+pub use crate::uniforms::{Dimensions, JumpDist};
 
 pub struct JfaPipeline {
     cached: CachedRenderPipelineId,
@@ -51,8 +31,10 @@ impl FromWorld for JfaPipeline {
         let dimensions_bind_group_layout = res.dimensions_bind_group_layout.clone();
         let jfa_bind_group_layout = res.jfa_bind_group_layout.clone();
 
+        let settings = world.resource::<WaterEffectSettings>();
+        let format = settings.jfa_format();
         let asset_server = world.resource::<AssetServer>();
-        let shader = asset_server.load("shaders/jfa.wgsl");
+        let shader = asset_server.load(&settings.shaders.jfa);
 
         let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
         let cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
@@ -69,7 +51,7 @@ impl FromWorld for JfaPipeline {
                 shader_defs: vec![],
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: JFA_TEXTURE_FORMAT,
+                    format,
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
@@ -79,12 +61,45 @@ impl FromWorld for JfaPipeline {
             multisample: MultisampleState::default(),
         });
 
+        world.resource_mut::<PipelineTracker>().track("jfa", cached);
+
         JfaPipeline { cached }
     }
 }
 
+/// Exponent of the longest jump, for the jump flood to reach
+/// `distance_from_coast` view pixels away from the coast.
+pub(crate) fn max_jump_exponent(view_res: &ViewWaterEffectResources, distance_from_coast: f32) -> usize {
+    // The style distance is in view pixels, the jump flood may run at a
+    // lower resolution.
+    let dims = &view_res.jfa_dimensions;
+    let jfa_scale = dims.width / view_res.dimensions.width;
+    let max_exp = jump_exponent(dims.width, dims.height, distance_from_coast * jfa_scale);
+    verbose!("jfa over {} px in {} passes", 1_u32 << max_exp, max_exp + 1);
+    max_exp
+}
+
+/// Exponent of the longest jump for a `width` × `height` jump flood to reach
+/// `distance` of its own pixels.
+pub(crate) fn jump_exponent(width: f32, height: f32, distance: f32) -> usize {
+    let width = width.max(height).min(distance.ceil());
+
+    // The half-width of the JFA region is 2^(max_exp + 1) - 1.
+    //
+    // weight < 2^(max_exp + 1) - 1
+    // weight + 1 < 2^(max_exp + 1)
+    // log2(weight + 1) < max_exp + 1
+    // max_exp > log2(weight + 1) - 1
+
+    //let max_exp = width.log2().ceil() as usize;
+    width.log2() as usize
+}
+
 pub struct JfaNode {
-    query: QueryState<&'static Handle<RipplesStyle>>,
+    query: QueryState<(
+        &'static Handle<RipplesStyle>,
+        &'static ViewWaterEffectResources,
+    )>,
 }
 
 impl FromWorld for JfaNode {
@@ -123,47 +138,34 @@ impl Node for JfaNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
+        let _span = bevy::log::info_span!("jfa").entered();
+
         let res = world.resource::<WaterEffectResources>();
-        graph
-            .set_output(Self::OUT_JUMP, res.jfa_final_output.default_view.clone())
-            .unwrap();
+        let error_reporter = world.resource::<ErrorReporter>();
+
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        // The driver only runs the water effect for views with resources.
+        let (ripples_style, view_res) = match self.query.get_manual(world, view_entity) {
+            Ok(view) => view,
+            Err(_) => {
+                error_reporter.report(WaterEffectError::MissingCamera(view_entity));
+                return Ok(());
+            }
+        };
+        graph.set_output(Self::OUT_JUMP, view_res.jfa.final_output.default_view.clone())?;
 
         let styles = world.resource::<RenderAssets<RipplesStyle>>();
-        let width = match self
-            .query
-            .get_manual(world, graph.get_input_entity(Self::IN_VIEW)?)
-        {
-            Ok(ripples_style) => {
-                let dims = res.dimensions_buffer.get();
-                dims.width.max(dims.height).min(
-                   styles
-                        .get(&ripples_style)
-                        .unwrap()
-                        .params
-                        .distance_from_coast
-                        .ceil(),
-                )
+        let style = match view_ripples_params(styles, world.resource::<BlendedRipplesParams>(), view_entity, ripples_style) {
+            Some(style) => style,
+            None => {
+                error_reporter.report_missing_style(world.resource::<AssetServer>(), ripples_style);
+                return Ok(());
             }
-            Err(_) => return Ok(()),
         };
 
-        dbg!(&width);
-
         let pipeline = world.get_resource::<JfaPipeline>().unwrap();
         let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
 
-        let pipeline_state = pipeline_cache.get_render_pipeline_state(pipeline.cached);
-        
-        match pipeline_state {
-            CachedPipelineState::Ok(_) => {
-                bevy::log::info!("jfa pipeline state is Ok");
-            }
-            _ => {
-                bevy::log::warn!("jfa pipeline state is not Ok");
-                dbg!(&pipeline_state);
-            },
-        }
-
         let cached_pipeline = match pipeline_cache.get_render_pipeline(pipeline.cached) {
             Some(c) => c,
             // Still queued.
@@ -171,82 +173,89 @@ impl Node for JfaNode {
                 return Ok(());
             }
         };
+        // Nothing was seeded this frame, the floods would read stale textures.
+        let init_pipeline = world.resource::<JfaInitPipeline>();
+        let mask_pipeline = world.resource::<WaterMaskPipeline>();
+        if init_pipeline.ready(mask_pipeline, pipeline_cache, view_res.inverted_jfa.is_some()).is_none() {
+            return Ok(());
+        }
 
-        // The half-width of the JFA region is 2^(max_exp + 1) - 1.
-        //
-        // weight < 2^(max_exp + 1) - 1
-        // weight + 1 < 2^(max_exp + 1)
-        // log2(weight + 1) < max_exp + 1
-        // max_exp > log2(weight + 1) - 1
-
-        let max_exp = width.log2() as usize;
-
-        dbg!(max_exp);
-
-        //let max_exp = width.log2().ceil() as usize;
-        for it in 0..=max_exp {
-            let exp = max_exp - it;
+        let max_exp = max_jump_exponent(view_res, style.params.distance_from_coast);
 
-            dbg!(it);
-            dbg!(&exp);
+        run_flood(render_context, res, view_res, &view_res.jfa, cached_pipeline, max_exp);
+        if let Some(inverted_jfa) = &view_res.inverted_jfa {
+            let _span = bevy::log::info_span!("inverted_jfa").entered();
+            run_flood(render_context, res, view_res, inverted_jfa, cached_pipeline, max_exp);
+        }
 
-            let target: &TextureView;
-            let src: &BindGroup;
+        Ok(())
+    }
+}
 
-            if it % 2 == 1 {
-                if it == max_exp {
-                    target = &res.jfa_final_output.default_view;
-                } else {
-                    target = &res.jfa_primary_output.default_view;
-                }
-                src = &res.jfa_from_secondary_bind_group;
+/// Floods from the primary output of `flood`, written by the JFA init pass,
+/// into its final output.
+fn run_flood(
+    render_context: &mut RenderContext,
+    res: &WaterEffectResources,
+    view_res: &ViewWaterEffectResources,
+    flood: &JfaFlood,
+    pipeline: &RenderPipeline,
+    max_exp: usize,
+) {
+    for it in 0..=max_exp {
+        let exp = max_exp - it;
+        let _iteration_span =
+            bevy::log::info_span!("jfa_iteration", jump_distance = 1_u32 << exp).entered();
+
+        let target: &TextureView;
+        let src: &BindGroup;
+
+        if it % 2 == 1 {
+            if it == max_exp {
+                target = &flood.final_output.default_view;
             } else {
-                if it == max_exp {
-                    target = &res.jfa_final_output.default_view;
-                } else {
-                    target = &res.jfa_secondary_output.default_view;
-                }
-                src = &res.jfa_from_primary_bind_group;
+                target = &flood.primary_output.default_view;
             }
-
-            // dbg!(src);
-
-            let attachment = RenderPassColorAttachment {
-                view: target,
-                resolve_target: None,
-                ops: Operations {
-                    // TODO: ideally, this would be the equivalent of DONT_CARE, but wgpu doesn't expose that.
-                    load: LoadOp::Clear(
-                        Color::RgbaLinear {
-                            red: -1.0,
-                            green: -1.0,
-                            blue: 0.0,
-                            alpha: 0.0,
-                        }
-                        .into(),
-                    ),
-                    store: true,
-                },
-            };
-            let render_pass =
-                render_context
-                    .command_encoder
-                    .begin_render_pass(&RenderPassDescriptor {
-                        label: Some("water_effect_jfa"),
-                        color_attachments: &[Some(attachment)],
-                        depth_stencil_attachment: None,
-                    });
-
-            // dbg!(&render_pass);
-            dbg!(&res.jfa_distance_offsets[exp]);
-
-            let mut tracked_pass = TrackedRenderPass::new(render_pass);
-            tracked_pass.set_render_pipeline(cached_pipeline);
-            tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-            tracked_pass.set_bind_group(1, src, &[res.jfa_distance_offsets[exp]]);
-            tracked_pass.draw(0..3, 0..1);
+            src = &flood.from_secondary_bind_group;
+        } else {
+            if it == max_exp {
+                target = &flood.final_output.default_view;
+            } else {
+                target = &flood.secondary_output.default_view;
+            }
+            src = &flood.from_primary_bind_group;
         }
 
-        Ok(())
+        let attachment = RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: Operations {
+                // TODO: ideally, this would be the equivalent of DONT_CARE, but wgpu doesn't expose that.
+                load: LoadOp::Clear(
+                    Color::RgbaLinear {
+                        red: -1.0,
+                        green: -1.0,
+                        blue: 0.0,
+                        alpha: 0.0,
+                    }
+                    .into(),
+                ),
+                store: true,
+            },
+        };
+        let render_pass =
+            render_context
+                .command_encoder
+                .begin_render_pass(&RenderPassDescriptor {
+                    label: Some("water_effect_jfa"),
+                    color_attachments: &[Some(attachment)],
+                    depth_stencil_attachment: None,
+                });
+
+        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        tracked_pass.set_render_pipeline(pipeline);
+        tracked_pass.set_bind_group(0, &view_res.jfa_dimensions_bind_group, &[]);
+        tracked_pass.set_bind_group(1, src, &[res.jfa_distance_offsets[exp]]);
+        tracked_pass.draw(0..3, 0..1);
     }
 }
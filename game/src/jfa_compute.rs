@@ -0,0 +1,451 @@
+//! Compute shader implementation of the JFA init and jump flood passes,
+//! selected with [`JfaBackend::Compute`](crate::JfaBackend::Compute).
+//!
+//! The nodes have the same slots as [`JfaInitNode`] and [`JfaNode`], so the
+//! rest of the water effect graph does not depend on the backend.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        texture::CachedTexture,
+    },
+};
+
+use crate::{
+    error::{ErrorReporter, WaterEffectError},
+    jfa::{max_jump_exponent, JfaNode},
+    jfa_init::JfaInitNode,
+    mask::WaterMaskPipeline,
+    resources::{JfaFlood, ViewWaterEffectResources, WaterEffectResources},
+    ripples_style::RipplesStyle,
+    settings::WaterEffectSettings,
+    status::PipelineTracker,
+    style_transition::{view_ripples_params, BlendedRipplesParams},
+    uniforms::Dimensions,
+};
+
+/// Must match `@workgroup_size` in `jfa_compute.wgsl`.
+const WORKGROUP_SIZE: u32 = 8;
+
+pub struct JfaComputePipelines {
+    init: CachedComputePipelineId,
+    // Seeds the flood from the water, with
+    // `WaterEffectSettings::distance_field_output`.
+    inverted_init: Option<CachedComputePipelineId>,
+    flood: CachedComputePipelineId,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl FromWorld for JfaComputePipelines {
+    fn from_world(world: &mut World) -> Self {
+        let res = world.resource::<WaterEffectResources>();
+        let dims_layout = res.dimensions_bind_group_layout.clone();
+
+        let device = world.resource::<RenderDevice>();
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("water_effect_jfa_compute_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(crate::jfa::JumpDist::min_size()),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: TextureFormat::Rg32Float,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let settings = world.resource::<WaterEffectSettings>();
+        let asset_server = world.resource::<AssetServer>();
+        let shader = asset_server.load(&settings.shaders.jfa_compute);
+        let distance_field_output = settings.distance_field_output;
+
+        let layout = Some(vec![dims_layout, bind_group_layout.clone()]);
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let init = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("water_effect_jfa_init_compute_pipeline".into()),
+            layout: layout.clone(),
+            shader: shader.clone(),
+            shader_defs: vec![],
+            entry_point: "init".into(),
+        });
+        let inverted_init = distance_field_output.then(|| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("water_effect_inverted_jfa_init_compute_pipeline".into()),
+                layout: layout.clone(),
+                shader: shader.clone(),
+                shader_defs: vec!["INVERT_MASK".into()],
+                entry_point: "init".into(),
+            })
+        });
+        let flood = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("water_effect_jfa_compute_pipeline".into()),
+            layout,
+            shader,
+            shader_defs: vec![],
+            entry_point: "flood".into(),
+        });
+
+        let mut tracker = world.resource_mut::<PipelineTracker>();
+        tracker.track("jfa_init", init);
+        tracker.track("jfa", flood);
+        if let Some(inverted_init) = inverted_init {
+            tracker.track("inverted_jfa_init", inverted_init);
+        }
+
+        JfaComputePipelines {
+            init,
+            inverted_init,
+            flood,
+            bind_group_layout,
+        }
+    }
+}
+
+impl JfaComputePipelines {
+    /// The init pipeline, and the inverted one if `inverted`, once all of
+    /// them and the `mask` pipeline are compiled: both floods are seeded from
+    /// the mask of the frame in the same frame, or neither is.
+    fn ready_init<'a>(
+        &self,
+        mask: &WaterMaskPipeline,
+        pipeline_cache: &'a PipelineCache,
+        inverted: bool,
+    ) -> Option<(&'a ComputePipeline, Option<&'a ComputePipeline>)> {
+        if !mask.is_ready(pipeline_cache) {
+            return None;
+        }
+        let pipeline = pipeline_cache.get_compute_pipeline(self.init)?;
+        let inverted = if inverted {
+            Some(self.inverted_init.and_then(|id| pipeline_cache.get_compute_pipeline(id))?)
+        } else {
+            None
+        };
+        Some((pipeline, inverted))
+    }
+}
+
+/// Bind groups of the compute passes of a view, created along with its
+/// textures in [`ViewWaterEffectResources`].
+#[derive(Clone)]
+pub struct ViewJfaComputeBindGroups {
+    jfa: JfaComputeBindGroups,
+    inverted_jfa: Option<JfaComputeBindGroups>,
+}
+
+/// Bind groups of the compute passes of one flood, named after the texture
+/// they read from and the one they write to.
+#[derive(Clone)]
+struct JfaComputeBindGroups {
+    mask_to_primary: BindGroup,
+    primary_to_secondary: BindGroup,
+    secondary_to_primary: BindGroup,
+    primary_to_final: BindGroup,
+    secondary_to_final: BindGroup,
+}
+
+impl ViewJfaComputeBindGroups {
+    pub(crate) fn new(
+        device: &RenderDevice,
+        pipelines: &JfaComputePipelines,
+        water_effect: &WaterEffectResources,
+        mask_output: &CachedTexture,
+        jfa: &JfaFlood,
+        inverted_jfa: Option<&JfaFlood>,
+    ) -> ViewJfaComputeBindGroups {
+        let create_bind_group = |label: &str, src: &TextureView, dst: &TextureView| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some(label),
+                layout: &pipelines.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: water_effect.jfa_distance_buffer.binding().unwrap(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(src),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&water_effect.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(dst),
+                    },
+                ],
+            })
+        };
+
+        let mask = &mask_output.default_view;
+        let create_flood_bind_groups = |flood: &JfaFlood| {
+            let primary = &flood.primary_output.default_view;
+            let secondary = &flood.secondary_output.default_view;
+            let final_output = &flood.final_output.default_view;
+
+            JfaComputeBindGroups {
+                mask_to_primary: create_bind_group("jfa_compute_mask_to_primary", mask, primary),
+                primary_to_secondary: create_bind_group("jfa_compute_primary_to_secondary", primary, secondary),
+                secondary_to_primary: create_bind_group("jfa_compute_secondary_to_primary", secondary, primary),
+                primary_to_final: create_bind_group("jfa_compute_primary_to_final", primary, final_output),
+                secondary_to_final: create_bind_group("jfa_compute_secondary_to_final", secondary, final_output),
+            }
+        };
+
+        ViewJfaComputeBindGroups {
+            jfa: create_flood_bind_groups(jfa),
+            inverted_jfa: inverted_jfa.map(create_flood_bind_groups),
+        }
+    }
+}
+
+fn dispatch_view(pass: &mut ComputePass, dims: &Dimensions) {
+    pass.dispatch_workgroups(
+        (dims.width as u32).div_ceil(WORKGROUP_SIZE),
+        (dims.height as u32).div_ceil(WORKGROUP_SIZE),
+        1,
+    );
+}
+
+/// Compute counterpart of [`JfaInitNode`].
+pub struct JfaInitComputeNode {
+    query: QueryState<&'static ViewWaterEffectResources>,
+}
+
+impl FromWorld for JfaInitComputeNode {
+    fn from_world(world: &mut World) -> Self {
+        JfaInitComputeNode {
+            query: QueryState::from_world(world),
+        }
+    }
+}
+
+impl Node for JfaInitComputeNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![
+            SlotInfo::new(JfaInitNode::IN_VIEW, SlotType::Entity),
+            SlotInfo::new(JfaInitNode::IN_MASK, SlotType::TextureView),
+        ]
+    }
+
+    fn output(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(JfaInitNode::OUT_JFA_INIT, SlotType::TextureView)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let _span = bevy::log::info_span!("jfa_init").entered();
+
+        let view_entity = graph.get_input_entity(JfaInitNode::IN_VIEW)?;
+        // The driver only runs the water effect for views with resources.
+        let view_res = match self.query.get_manual(world, view_entity) {
+            Ok(view) => view,
+            Err(_) => return Ok(()),
+        };
+        graph.set_output(
+            JfaInitNode::OUT_JFA_INIT,
+            view_res.jfa.primary_output.default_view.clone(),
+        )?;
+        let bind_groups = match &view_res.jfa_compute {
+            Some(bind_groups) => bind_groups,
+            None => return Ok(()),
+        };
+
+        let pipelines = world.resource::<JfaComputePipelines>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let mask = world.resource::<WaterMaskPipeline>();
+        let (pipeline, inverted_pipeline) =
+            match pipelines.ready_init(mask, pipeline_cache, bind_groups.inverted_jfa.is_some()) {
+                Some(p) => p,
+                // Still queued.
+                None => return Ok(()),
+            };
+
+        let res = world.resource::<WaterEffectResources>();
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("water_effect_jfa_init_compute"),
+            });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &view_res.jfa_dimensions_bind_group, &[]);
+        pass.set_bind_group(1, &bind_groups.jfa.mask_to_primary, &[res.jfa_distance_offsets[0]]);
+        dispatch_view(&mut pass, &view_res.jfa_dimensions);
+
+        if let (Some(pipeline), Some(inverted_bind_groups)) = (inverted_pipeline, &bind_groups.inverted_jfa) {
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(1, &inverted_bind_groups.mask_to_primary, &[res.jfa_distance_offsets[0]]);
+            dispatch_view(&mut pass, &view_res.jfa_dimensions);
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute counterpart of [`JfaNode`].
+pub struct JfaComputeNode {
+    query: QueryState<(
+        &'static Handle<RipplesStyle>,
+        &'static ViewWaterEffectResources,
+    )>,
+}
+
+impl FromWorld for JfaComputeNode {
+    fn from_world(world: &mut World) -> Self {
+        JfaComputeNode {
+            query: QueryState::from_world(world),
+        }
+    }
+}
+
+impl Node for JfaComputeNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![
+            SlotInfo::new(JfaNode::IN_VIEW, SlotType::Entity),
+            SlotInfo::new(JfaNode::IN_BASE, SlotType::TextureView),
+        ]
+    }
+
+    fn output(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(JfaNode::OUT_JUMP, SlotType::TextureView)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let _span = bevy::log::info_span!("jfa").entered();
+
+        let error_reporter = world.resource::<ErrorReporter>();
+
+        let view_entity = graph.get_input_entity(JfaNode::IN_VIEW)?;
+        // The driver only runs the water effect for views with resources.
+        let (ripples_style, view_res) = match self.query.get_manual(world, view_entity) {
+            Ok(view) => view,
+            Err(_) => {
+                error_reporter.report(WaterEffectError::MissingCamera(view_entity));
+                return Ok(());
+            }
+        };
+        graph.set_output(JfaNode::OUT_JUMP, view_res.jfa.final_output.default_view.clone())?;
+        let bind_groups = match &view_res.jfa_compute {
+            Some(bind_groups) => bind_groups,
+            None => return Ok(()),
+        };
+
+        let styles = world.resource::<RenderAssets<RipplesStyle>>();
+        let style = match view_ripples_params(styles, world.resource::<BlendedRipplesParams>(), view_entity, ripples_style) {
+            Some(style) => style,
+            None => {
+                error_reporter.report_missing_style(world.resource::<AssetServer>(), ripples_style);
+                return Ok(());
+            }
+        };
+
+        let pipelines = world.resource::<JfaComputePipelines>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let mask = world.resource::<WaterMaskPipeline>();
+        let pipeline = match pipeline_cache.get_compute_pipeline(pipelines.flood) {
+            Some(p) => p,
+            // Still queued.
+            None => return Ok(()),
+        };
+        // Nothing was seeded this frame, the floods would read stale textures.
+        if pipelines.ready_init(mask, pipeline_cache, bind_groups.inverted_jfa.is_some()).is_none() {
+            return Ok(());
+        }
+
+        let res = world.resource::<WaterEffectResources>();
+        let max_exp = max_jump_exponent(view_res, style.params.distance_from_coast);
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("water_effect_jfa_compute"),
+            });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &view_res.jfa_dimensions_bind_group, &[]);
+
+        flood(&mut pass, res, view_res, &bind_groups.jfa, max_exp);
+        if let Some(inverted_bind_groups) = &bind_groups.inverted_jfa {
+            let _span = bevy::log::info_span!("inverted_jfa").entered();
+            flood(&mut pass, res, view_res, inverted_bind_groups, max_exp);
+        }
+
+        Ok(())
+    }
+}
+
+/// Floods from the primary texture of a flood into its final one, with the
+/// flood pipeline already set on `pass`.
+fn flood<'a>(
+    pass: &mut ComputePass<'a>,
+    res: &WaterEffectResources,
+    view_res: &ViewWaterEffectResources,
+    bind_groups: &'a JfaComputeBindGroups,
+    max_exp: usize,
+) {
+    // Same ping-pong as `JfaNode`: JFA init wrote the primary texture.
+    for it in 0..=max_exp {
+        let exp = max_exp - it;
+        let _iteration_span =
+            bevy::log::info_span!("jfa_iteration", jump_distance = 1_u32 << exp).entered();
+
+        let bind_group = match (it % 2 == 1, it == max_exp) {
+            (false, false) => &bind_groups.primary_to_secondary,
+            (false, true) => &bind_groups.primary_to_final,
+            (true, false) => &bind_groups.secondary_to_primary,
+            (true, true) => &bind_groups.secondary_to_final,
+        };
+        pass.set_bind_group(1, bind_group, &[res.jfa_distance_offsets[exp]]);
+        dispatch_view(pass, &view_res.jfa_dimensions);
+    }
+}
@@ -7,15 +7,19 @@ use bevy::{
         renderer::RenderContext,
     },
 };
-use bevy::render::render_resource::TextureFormat;
-use bevy::render::texture::BevyDefault;
-
-use crate::{resources::WaterEffectResources, 
-    JFA_TEXTURE_FORMAT
+use crate::{
+    mask::WaterMaskPipeline,
+    resources::{ViewWaterEffectResources, WaterEffectResources},
+    settings::WaterEffectSettings,
+    status::PipelineTracker,
+    FULLSCREEN_PRIMITIVE_STATE,
 };
 
 pub struct JfaInitPipeline {
     cached: CachedRenderPipelineId,
+    // Seeds the flood from the water, with
+    // `WaterEffectSettings::distance_field_output`.
+    inverted: Option<CachedRenderPipelineId>,
 }
 
 impl FromWorld for JfaInitPipeline {
@@ -24,144 +28,197 @@ impl FromWorld for JfaInitPipeline {
         let dims_layout = res.dimensions_bind_group_layout.clone();
         let init_layout = res.jfa_init_bind_group_layout.clone();
 
+        let settings = world.resource::<WaterEffectSettings>();
+        let format = settings.jfa_format();
+        let distance_field_output = settings.distance_field_output;
         let asset_server = world.resource::<AssetServer>();
-        let shader = asset_server.load("shaders/jfa_init.wgsl");
+        let shader = asset_server.load(&settings.shaders.jfa_init);
 
-        let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
-        let cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
-            label: Some("water_effect_jfa_init_pipeline".into()),
-            layout: Some(vec![dims_layout, init_layout]),
+        let descriptor = |label: &'static str, shader_defs: Vec<String>| RenderPipelineDescriptor {
+            label: Some(label.into()),
+            layout: Some(vec![dims_layout.clone(), init_layout.clone()]),
             vertex: VertexState {
                 shader: shader.clone(),
                 shader_defs: vec![],
                 entry_point: "vertex".into(),
                 buffers: vec![],
             },
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
+            primitive: FULLSCREEN_PRIMITIVE_STATE,
             depth_stencil: None,
             multisample: MultisampleState::default(),
             fragment: Some(FragmentState {
                 shader: shader.clone(),
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: JFA_TEXTURE_FORMAT,
+                    format,
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
             }),
+        };
+
+        let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
+        let cached = pipeline_cache.queue_render_pipeline(descriptor("water_effect_jfa_init_pipeline", vec![]));
+        let inverted = distance_field_output.then(|| {
+            pipeline_cache.queue_render_pipeline(descriptor(
+                "water_effect_inverted_jfa_init_pipeline",
+                vec!["INVERT_MASK".into()],
+            ))
         });
 
-        JfaInitPipeline { cached }
+        let mut tracker = world.resource_mut::<PipelineTracker>();
+        tracker.track("jfa_init", cached);
+        if let Some(inverted) = inverted {
+            tracker.track("inverted_jfa_init", inverted);
+        }
+
+        JfaInitPipeline { cached, inverted }
+    }
+}
+
+impl JfaInitPipeline {
+    /// The init pipeline, and the inverted one if `inverted`, once all of
+    /// them and the `mask` pipeline are compiled: both floods are seeded from
+    /// the mask of the frame in the same frame, or neither is.
+    pub(crate) fn ready<'a>(
+        &self,
+        mask: &WaterMaskPipeline,
+        pipeline_cache: &'a PipelineCache,
+        inverted: bool,
+    ) -> Option<(&'a RenderPipeline, Option<&'a RenderPipeline>)> {
+        if !mask.is_ready(pipeline_cache) {
+            return None;
+        }
+        let pipeline = pipeline_cache.get_render_pipeline(self.cached)?;
+        let inverted = if inverted {
+            Some(self.inverted.and_then(|id| pipeline_cache.get_render_pipeline(id))?)
+        } else {
+            None
+        };
+        Some((pipeline, inverted))
     }
 }
 
 /// Render graph node for the JFA initialization pass.
-pub struct JfaInitNode;
+pub struct JfaInitNode {
+    query: QueryState<&'static ViewWaterEffectResources>,
+}
+
+impl FromWorld for JfaInitNode {
+    fn from_world(world: &mut World) -> Self {
+        JfaInitNode {
+            query: QueryState::from_world(world),
+        }
+    }
+}
 
 impl JfaInitNode {
-    /// The input stencil buffer.
+    pub const IN_VIEW: &'static str = "in_view";
+
+    /// The input water mask.
     ///
-    /// This should have the format `TextureFormat::Depth24PlusStencil8`.
-    /// Fragments in the JFA initialization pass will pass the stencil test if
-    /// the corresponding stencil buffer value is 255, and fail otherwise.
-    /// The depth aspect is ignored.
-    pub const IN_MASK: &'static str = "in_stencil";
+    /// This should have the format `TextureFormat::R8Unorm` and hold the land
+    /// coverage of each fragment, as produced by the mask pass.
+    pub const IN_MASK: &'static str = "in_mask";
 
     /// The produced initialized JFA buffer.
     ///
-    /// This has the format `bevy_jfa::JFA_TEXTURE_FORMAT`. Fragments that pass
-    /// the stencil test are assigned their framebuffer coordinates. Fragments
-    /// that fail the stencil test are assigned a value of (-1, -1).
+    /// This has the format `WaterEffectSettings::jfa_texture_format`, or
+    /// `Rg32Float` with `JfaBackend::Compute`, whose init node outputs this
+    /// slot too. Fully covered fragments are assigned their framebuffer
+    /// coordinates, partially covered ones are moved towards the uncovered
+    /// side, and uncovered ones are assigned a value of (-1, -1).
     pub const OUT_JFA_INIT: &'static str = "out_jfa_init";
 }
 
 impl Node for JfaInitNode {
     fn input(&self) -> Vec<SlotInfo> {
-        vec![SlotInfo::new(Self::IN_MASK, SlotType::TextureView)]
+        vec![
+            SlotInfo::new(Self::IN_VIEW, SlotType::Entity),
+            SlotInfo::new(Self::IN_MASK, SlotType::TextureView),
+        ]
     }
 
     fn output(&self) -> Vec<SlotInfo> {
         vec![SlotInfo::new(Self::OUT_JFA_INIT, SlotType::TextureView)]
     }
 
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
     fn run(
         &self,
         graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        let res = world.get_resource::<WaterEffectResources>().unwrap();
-        graph
-            .set_output(
-                Self::OUT_JFA_INIT,
-                res.jfa_primary_output.default_view.clone(),
-            )
-            .unwrap();
+        let _span = bevy::log::info_span!("jfa_init").entered();
+
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
+        // The driver only runs the water effect for views with resources.
+        let res = match self.query.get_manual(world, view_entity) {
+            Ok(res) => res,
+            Err(_) => return Ok(()),
+        };
+        graph.set_output(
+            Self::OUT_JFA_INIT,
+            res.jfa.primary_output.default_view.clone(),
+        )?;
 
         let pipeline = world.get_resource::<JfaInitPipeline>().unwrap();
         let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
 
-        let pipelines = world.get_resource::<PipelineCache>().unwrap();
-        let pipeline_state = pipelines.get_render_pipeline_state(pipeline.cached);
-
-        match pipeline_state {
-            CachedPipelineState::Ok(_) => {
-                bevy::log::info!("jfa init pipeline state is Ok");
-            }
-            _ => {
-                bevy::log::warn!("jfa init pipeline state is not Ok");
-                dbg!(&pipeline_state);
-            },
+        let mask_pipeline = world.resource::<WaterMaskPipeline>();
+        let (cached_pipeline, inverted_pipeline) =
+            match pipeline.ready(mask_pipeline, pipeline_cache, res.inverted_jfa.is_some()) {
+                Some(pipelines) => pipelines,
+                // Still queued.
+                None => return Ok(()),
+            };
+        run_init_pass(render_context, res, cached_pipeline, &res.jfa.primary_output.default_view);
+
+        if let (Some(inverted_jfa), Some(inverted_pipeline)) = (&res.inverted_jfa, inverted_pipeline) {
+            run_init_pass(render_context, res, inverted_pipeline, &inverted_jfa.primary_output.default_view);
         }
 
-        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipeline.cached) {
-            Some(c) => c,
-            // Still queued.
-            None => {
-                return Ok(());
-            }
-        };
-
-        let render_pass = render_context
-            .command_encoder
-            .begin_render_pass(&RenderPassDescriptor {
-                label: Some("water_effect_jfa_init"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &res.jfa_primary_output.default_view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(
-                            Color::RgbaLinear {
-                                red: -1.0,
-                                green: -1.0,
-                                blue: 0.0,
-                                alpha: 0.0,
-                            }
-                            .into(),
-                        ),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-        // dbg!(&render_pass);
-
-        let mut tracked_pass = TrackedRenderPass::new(render_pass);
-        tracked_pass.set_render_pipeline(cached_pipeline);
-        tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-        tracked_pass.set_bind_group(1, &res.jfa_init_bind_group, &[]);
-        tracked_pass.draw(0..3, 0..1);
-
         Ok(())
     }
 }
+
+fn run_init_pass(
+    render_context: &mut RenderContext,
+    res: &ViewWaterEffectResources,
+    pipeline: &RenderPipeline,
+    target: &TextureView,
+) {
+    let render_pass = render_context
+        .command_encoder
+        .begin_render_pass(&RenderPassDescriptor {
+            label: Some("water_effect_jfa_init"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(
+                        Color::RgbaLinear {
+                            red: -1.0,
+                            green: -1.0,
+                            blue: 0.0,
+                            alpha: 0.0,
+                        }
+                        .into(),
+                    ),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+    let mut tracked_pass = TrackedRenderPass::new(render_pass);
+    tracked_pass.set_render_pipeline(pipeline);
+    tracked_pass.set_bind_group(0, &res.jfa_dimensions_bind_group, &[]);
+    tracked_pass.set_bind_group(1, &res.jfa_init_bind_group, &[]);
+    tracked_pass.draw(0..3, 0..1);
+}
@@ -0,0 +1,332 @@
+use bevy::math::Vec2;
+
+use crate::jfa::jump_exponent;
+
+/// CPU version of the jump flood, for checking the shaders.
+///
+/// [`JfaReference::init`] mirrors `jfa_init.wgsl`, [`JfaReference::step`]
+/// mirrors `jfa.wgsl`, and [`JfaReference::run`] chains them the way
+/// `JfaNode::run` does. Samples are read with the same nearest, clamp to edge
+/// sampling as the GPU passes, and positions are kept in `f32` like the
+/// compute backend stores them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JfaReference {
+    width: u32,
+    height: u32,
+    /// Framebuffer-space position of the closest initial fragment for each
+    /// pixel, row by row, or (-1, -1).
+    seeds: Vec<Vec2>,
+}
+
+const INVALID: Vec2 = Vec2::new(-1.0, -1.0);
+
+impl JfaReference {
+    /// Seeds a jump flood from a `width` × `height` mask, row by row, with
+    /// values in `0.0..=1.0`.
+    ///
+    /// Pixels above 0.99 are seeds at their centre, pixels in between are
+    /// moved along the mask gradient by their uncovered fraction of a pixel.
+    pub fn init(mask: &[f32], width: u32, height: u32) -> Self {
+        assert_eq!(mask.len(), (width * height) as usize, "mask size");
+
+        let (dx, dy) = (1.0 / width as f32, 1.0 / height as f32);
+        let sample = |x: i64, y: i64| -> f32 {
+            let x = x.clamp(0, width as i64 - 1);
+            let y = y.clamp(0, height as i64 - 1);
+            mask[(y * width as i64 + x) as usize]
+        };
+
+        let mut seeds = Vec::with_capacity(mask.len());
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let texcoord = Vec2::new((x as f32 + 0.5) * dx, (y as f32 + 0.5) * dy);
+
+                // Same layout as the shader's mat3x3: samples[column][row].
+                let mut samples = [[0.0; 3]; 3];
+                for (i, column) in samples.iter_mut().enumerate() {
+                    for (j, value) in column.iter_mut().enumerate() {
+                        *value = sample(x + i as i64 - 1, y + j as i64 - 1);
+                    }
+                }
+
+                let center = samples[1][1];
+                if center > 0.99 {
+                    seeds.push(texcoord);
+                    continue;
+                }
+                if center < 0.01 {
+                    seeds.push(INVALID);
+                    continue;
+                }
+
+                let sobel_x = samples[0][0] + 2.0 * samples[0][1] + samples[0][2]
+                    - samples[2][0]
+                    - 2.0 * samples[2][1]
+                    - samples[2][2];
+                let sobel_y = samples[0][0] + 2.0 * samples[1][0] + samples[2][0]
+                    - samples[0][2]
+                    - 2.0 * samples[1][2]
+                    - samples[2][2];
+                let dir = -Vec2::new(sobel_x, sobel_y);
+
+                if dir.x.abs() < 0.005 && dir.y.abs() < 0.005 {
+                    seeds.push(texcoord);
+                    continue;
+                }
+
+                let offset = dir.normalize() * (1.0 - center) * Vec2::new(dx, dy);
+                seeds.push(texcoord + offset);
+            }
+        }
+
+        JfaReference {
+            width,
+            height,
+            seeds,
+        }
+    }
+
+    /// One jump flood pass with jumps of `jump` pixels.
+    pub fn step(&self, jump: u32) -> Self {
+        let fb_to_pix = Vec2::new(self.width as f32, self.height as f32);
+        let jump = jump as i64;
+
+        let mut seeds = Vec::with_capacity(self.seeds.len());
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let pix_coord = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                let mut min_dist2 = f32::MAX;
+                let mut min_dist2_pos = INVALID;
+                // Same order as the shader, so that ties resolve the same way.
+                for i in -1..=1 {
+                    for j in -1..=1 {
+                        let fb_sample = self.sample(x + i * jump, y + j * jump);
+                        let valid = fb_sample.x != -1.0;
+
+                        let delta = pix_coord - fb_sample * fb_to_pix;
+                        let dist2 = delta.dot(delta);
+
+                        if valid && dist2 < min_dist2 {
+                            min_dist2 = dist2;
+                            min_dist2_pos = fb_sample;
+                        }
+                    }
+                }
+                seeds.push(min_dist2_pos);
+            }
+        }
+
+        JfaReference {
+            width: self.width,
+            height: self.height,
+            seeds,
+        }
+    }
+
+    /// Seeds from `mask` and floods up to `max_distance` pixels away, with the
+    /// same passes as the render graph.
+    pub fn run(mask: &[f32], width: u32, height: u32, max_distance: f32) -> Self {
+        let max_exp = jump_exponent(width as f32, height as f32, max_distance);
+        (0..=max_exp)
+            .rev()
+            .fold(Self::init(mask, width, height), |jfa, exp| jfa.step(1 << exp))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Framebuffer-space position of the closest seed found for the pixel.
+    pub fn seed(&self, x: u32, y: u32) -> Option<Vec2> {
+        let seed = self.seeds[(y * self.width + x) as usize];
+        (seed.x != -1.0).then_some(seed)
+    }
+
+    /// Distance in pixels between the pixel centre and its closest seed, as
+    /// `ripples.wgsl` computes it.
+    pub fn distance(&self, x: u32, y: u32) -> Option<f32> {
+        let fb_to_pix = Vec2::new(self.width as f32, self.height as f32);
+        let pix_coord = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+        self.seed(x, y)
+            .map(|seed| pix_coord.distance(seed * fb_to_pix))
+    }
+
+    fn sample(&self, x: i64, y: i64) -> Vec2 {
+        let x = x.clamp(0, self.width as i64 - 1);
+        let y = y.clamp(0, self.height as i64 - 1);
+        self.seeds[(y * self.width as i64 + x) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 96;
+    const HEIGHT: u32 = 72;
+
+    /// Rasterizes `inside` with 4×4 supersampling, so that edges get partial
+    /// coverage like the filtered mask.
+    fn mask(inside: impl Fn(Vec2) -> bool) -> Vec<f32> {
+        let mut mask = Vec::with_capacity((WIDTH * HEIGHT) as usize);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let covered = (0..16)
+                    .filter(|i| {
+                        let sub = Vec2::new((i % 4) as f32 + 0.5, (i / 4) as f32 + 0.5) / 4.0;
+                        inside(Vec2::new(x as f32, y as f32) + sub)
+                    })
+                    .count();
+                mask.push(covered as f32 / 16.0);
+            }
+        }
+        mask
+    }
+
+    fn shapes() -> Vec<(&'static str, Vec<f32>)> {
+        vec![
+            ("circle", mask(|p| p.distance(Vec2::new(40.0, 30.0)) < 17.5)),
+            (
+                "rotated rectangle",
+                mask(|p| {
+                    let p = Vec2::from_angle(0.4).rotate(p - Vec2::new(50.0, 36.0));
+                    p.x.abs() < 22.0 && p.y.abs() < 9.0
+                }),
+            ),
+            (
+                "islands",
+                mask(|p| {
+                    p.distance(Vec2::new(12.0, 10.0)) < 6.0
+                        || p.distance(Vec2::new(80.0, 20.0)) < 9.0
+                        || p.distance(Vec2::new(30.0, 60.0)) < 4.0
+                        || (p.x > 60.0 && p.x < 90.0 && p.y > 50.0 && p.y < 66.0)
+                }),
+            ),
+            ("thin line", mask(|p| (p.y - 0.6 * p.x - 8.0).abs() < 0.7)),
+            ("single pixel", mask(|p| p.x as u32 == 70 && p.y as u32 == 11)),
+        ]
+    }
+
+    /// Exact distance from each pixel to the closest seed of `init`.
+    fn brute_force(init: &JfaReference) -> Vec<Option<f32>> {
+        let fb_to_pix = Vec2::new(init.width as f32, init.height as f32);
+        let seeds: Vec<Vec2> = (0..init.height)
+            .flat_map(|y| (0..init.width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| init.seed(x, y))
+            .map(|seed| seed * fb_to_pix)
+            .collect();
+
+        (0..init.height)
+            .flat_map(|y| (0..init.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let pix_coord = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                seeds
+                    .iter()
+                    .map(|seed| pix_coord.distance(*seed))
+                    .min_by(f32::total_cmp)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_brute_force_within_error_bound() {
+        // The jump flood is not exact: a pixel can inherit a seed that is
+        // slightly farther than the closest one. Its distance can never be
+        // shorter than the exact one.
+        const MAX_ERROR: f32 = 1.0;
+        const MAX_WRONG_FRACTION: f32 = 0.01;
+
+        for (name, mask) in shapes() {
+            let init = JfaReference::init(&mask, WIDTH, HEIGHT);
+            let exact = brute_force(&init);
+            let jfa = JfaReference::run(&mask, WIDTH, HEIGHT, f32::INFINITY);
+
+            let mut max_error = 0.0_f32;
+            let mut wrong = 0;
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let exact = exact[(y * WIDTH + x) as usize].expect("shape has seeds");
+                    let distance = jfa.distance(x, y).expect("jfa reached every pixel");
+                    assert!(
+                        distance >= exact - 1e-3,
+                        "{}: ({}, {}) closer than exact: {} < {}",
+                        name,
+                        x,
+                        y,
+                        distance,
+                        exact
+                    );
+
+                    let error = distance - exact;
+                    if error > 1e-3 {
+                        wrong += 1;
+                    }
+                    max_error = max_error.max(error);
+                }
+            }
+
+            let wrong_fraction = wrong as f32 / (WIDTH * HEIGHT) as f32;
+            assert!(max_error <= MAX_ERROR, "{}: max error {}", name, max_error);
+            assert!(
+                wrong_fraction <= MAX_WRONG_FRACTION,
+                "{}: {:.2}% of pixels off",
+                name,
+                wrong_fraction * 100.0
+            );
+        }
+    }
+
+    #[test]
+    fn binary_mask_seeds_pixel_centres() {
+        let mask = mask(|p| p.x >= 20.0 && p.x < 30.0 && p.y >= 10.0 && p.y < 20.0);
+        let init = JfaReference::init(&mask, WIDTH, HEIGHT);
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let expected = (20..30).contains(&x) && (10..20).contains(&y);
+                let seed = init.seed(x, y);
+                assert_eq!(seed.is_some(), expected, "({}, {})", x, y);
+                if let Some(seed) = seed {
+                    let centre =
+                        Vec2::new((x as f32 + 0.5) / WIDTH as f32, (y as f32 + 0.5) / HEIGHT as f32);
+                    assert!(seed.abs_diff_eq(centre, 1e-6), "({}, {}): {:?}", x, y, seed);
+                    assert!(init.distance(x, y).unwrap() < 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn partial_coverage_moves_seeds_along_the_gradient() {
+        // Land on the left, with a half covered column at x = 10.
+        let mask: Vec<f32> = (0..WIDTH * HEIGHT)
+            .map(|i| match i % WIDTH {
+                x if x < 10 => 1.0,
+                10 => 0.5,
+                _ => 0.0,
+            })
+            .collect();
+        let init = JfaReference::init(&mask, WIDTH, HEIGHT);
+
+        // The Sobel gradient points towards the land, the seed moves by the
+        // uncovered half pixel.
+        let seed = init.seed(10, 30).unwrap() * Vec2::new(WIDTH as f32, HEIGHT as f32);
+        assert!((seed.x - 10.0).abs() < 1e-4, "{:?}", seed);
+        assert!((seed.y - 30.5).abs() < 1e-4, "{:?}", seed);
+    }
+
+    #[test]
+    fn max_distance_limits_the_flood() {
+        let mask = mask(|p| p.distance(Vec2::new(10.0, 10.0)) < 3.0);
+        let jfa = JfaReference::run(&mask, WIDTH, HEIGHT, 8.0);
+
+        // Jumps of 8, 4, 2 and 1 reach at most 15 pixels away.
+        assert!(jfa.distance(20, 10).is_some());
+        assert!(jfa.distance(WIDTH - 1, HEIGHT - 1).is_none());
+    }
+}
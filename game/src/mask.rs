@@ -1,224 +1,92 @@
-use bevy::sprite::Mesh2dPipeline;
-use bevy::sprite::Mesh2dPipelineKey;
 use bevy::{
     prelude::*,
     render::{
-        mesh::InnerMeshVertexBufferLayout,
         render_graph::{Node, RenderGraphContext, SlotInfo, SlotType},
-        render_phase::{DrawFunctions, PhaseItem, RenderPhase, TrackedRenderPass},
+        render_phase::TrackedRenderPass,
         render_resource::*,
         renderer::RenderContext,
     },
-    utils::{FixedState, Hashed},
 };
-use bevy::render::render_phase::DrawFunctionId;
-use bevy::utils::FloatOrd;
-use bevy::render::render_phase::EntityPhaseItem;
-use bevy::render::render_phase::CachedRenderPipelinePhaseItem;
-use bevy::render::render_phase::SetItemPipeline;
-use bevy::sprite::SetMesh2dViewBindGroup;
-use bevy::sprite::SetMesh2dBindGroup;
-use bevy::sprite::DrawMesh2d;
-use bevy::render::render_phase::EntityRenderCommand;
-use bevy::ecs::system::lifetimeless::SRes;
-use bevy::ecs::system::SystemParamItem;
-use bevy::render::render_phase::RenderCommandResult;
-use bevy::sprite::RenderMaterials2d;
-use bevy::ecs::system::lifetimeless::SQuery;
-use bevy::ecs::system::lifetimeless::Read;
-use bevy::render::renderer::RenderDevice;
-use bevy::render::texture::BevyDefault;
-
-// use bevy::render::texture::BevyDefault;
-
-use crate::components::WaterSpritesMaterial;
-use crate::{resources::WaterEffectResources};
-
-#[derive(Debug)]
-pub struct WaterMask {
-    pub distance: f32,
-    pub pipeline: CachedRenderPipelineId,
-    pub entity: Entity,
-    pub draw_function: DrawFunctionId,
-}
-
-impl PhaseItem for WaterMask {
-    type SortKey = FloatOrd;
-
-    fn sort_key(&self) -> Self::SortKey {
-        dbg!(self.distance);
-        FloatOrd(self.distance)
-    }
-
-    fn draw_function(&self) -> DrawFunctionId {
-        dbg!(&self.draw_function);
-        self.draw_function
-    }
-}
-
-impl EntityPhaseItem for WaterMask {
-    fn entity(&self) -> Entity {
-        // TODO: this is called twice, why?
-        dbg!(&self.entity);
-        self.entity
-    }
-}
-
-impl CachedRenderPipelinePhaseItem for WaterMask {
-    fn cached_pipeline(&self) -> CachedRenderPipelineId {
-        dbg!(&self.pipeline);
-        self.pipeline
-    }
-}
-
-pub type DrawWaterMask = (
-    SetItemPipeline,
-    SetMesh2dViewBindGroup<0>,
-    SetWaterTextureViewBindGroup<1>,
-    SetMesh2dBindGroup<2>,
-    DrawMesh2d,
-);
-
-pub struct SetWaterTextureViewBindGroup<const I: usize>;
-
-impl<const I: usize> EntityRenderCommand for SetWaterTextureViewBindGroup<I> {
-    type Param = (SRes<RenderMaterials2d<WaterSpritesMaterial>>, SQuery<Read<Handle<WaterSpritesMaterial>>>);
-
-    fn render<'w>(
-        _view: Entity,
-        item: Entity,
-        (materials, query): SystemParamItem<'w, '_, Self::Param>,
-        pass: &mut TrackedRenderPass<'w>,
-    ) -> RenderCommandResult {
-        let material_2d = query.get(item).map(|material2d_handle| materials.into_inner().get(material2d_handle));
-        let result = match material_2d {
-            Ok(Some(material)) => {
-                pass.set_bind_group(I, &material.bind_group, &[]);
-                RenderCommandResult::Success
-            },
-            _ => RenderCommandResult::Failure
-        };
-
-        match result {
-            RenderCommandResult::Success => bevy::log::info!("render commands successful"),
-            RenderCommandResult::Failure => bevy::log::warn!("render command failed"),
-        }
-
-        result
-    }
-}
+use crate::{
+    resources::{ViewWaterEffectResources, WaterEffectResources},
+    settings::WaterEffectSettings,
+    status::PipelineTracker,
+    FULLSCREEN_PRIMITIVE_STATE,
+};
 
 pub struct WaterMaskPipeline {
-    mesh_pipeline: Mesh2dPipeline,
-    shader: Handle<Shader>,
-    texture_view_bind_group_layout: BindGroupLayout,
+    cached: CachedRenderPipelineId,
 }
 
 impl FromWorld for WaterMaskPipeline {
     fn from_world(world: &mut World) -> Self {
-        let mesh_pipeline = world.get_resource::<Mesh2dPipeline>().unwrap().clone();
+        let res = world.resource::<WaterEffectResources>();
+        let mask_src_layout = res.mask_src_bind_group_layout.clone();
 
+        let settings = world.resource::<WaterEffectSettings>();
+        let shader_defs = settings.mask_mode.shader_defs();
         let asset_server = world.resource::<AssetServer>();
-        let shader = asset_server.load("shaders/mask.wgsl");
-
-        // dbg!(&shader);
-
-        let render_device = world.resource::<RenderDevice>();
-        let texture_view_bind_group_layout = WaterSpritesMaterial::bind_group_layout(render_device);
-
-        // dbg!(&texture_view_bind_group_layout);
-
-        WaterMaskPipeline { mesh_pipeline, shader, texture_view_bind_group_layout }
-    }
-}
-
-impl SpecializedMeshPipeline for WaterMaskPipeline {
-    type Key = Mesh2dPipelineKey;
-
-    fn specialize(
-        &self,
-        key: Self::Key,
-        layout: &Hashed<InnerMeshVertexBufferLayout, FixedState>,
-    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-
-        dbg!(&key);
-
-        let blend = BlendState {
-            color: BlendComponent {
-                src_factor: BlendFactor::SrcAlpha,
-                dst_factor: BlendFactor::OneMinusSrcAlpha,
-                operation: BlendOperation::Add,
+        let shader = asset_server.load(&settings.shaders.mask);
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("water_effect_mask_pipeline".into()),
+            layout: Some(vec![mask_src_layout]),
+            vertex: VertexState {
+                shader: shader.clone(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
             },
-            alpha: BlendComponent {
-                src_factor: BlendFactor::One,
-                dst_factor: BlendFactor::Zero,
-                operation: BlendOperation::Add,
-            },
-        };
-
-        let mut desc = self.mesh_pipeline.specialize(key, layout)?;
-
-        desc.layout = Some(vec![
-            self.mesh_pipeline.view_layout.clone(),
-            self.texture_view_bind_group_layout.clone(),
-            self.mesh_pipeline.mesh_layout.clone(),
-        ]);
-
-        // desc.vertex.shader = self.shader.clone();
-
-        desc.fragment = Some(FragmentState {
-            shader: self.shader.clone(),
-            shader_defs: vec![],
-            entry_point: "fragment".into(),
-            // TODO: might want to remove for debugging
-            
-            targets: vec![Some(ColorTargetState {
-                format: TextureFormat::R8Unorm,
-                blend: None,
-                write_mask: ColorWrites::ALL,
-            })],
-            
-            // // TODO: added for debugging
-            // targets: vec![Some(ColorTargetState {
-            //     format: TextureFormat::bevy_default(),
-            //     blend: Some(blend),
-            //     write_mask: ColorWrites::ALL,
-            // })],
+            primitive: FULLSCREEN_PRIMITIVE_STATE,
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                shader,
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::R8Unorm,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
         });
-        desc.depth_stencil = None;
 
-        desc.multisample = MultisampleState {
-            count: 4,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        };
+        world.resource_mut::<PipelineTracker>().track("water_mask", cached);
 
-        desc.label = Some("water_mask_stencil_pipeline".into());
-
-        // dbg!(&desc);
+        WaterMaskPipeline { cached }
+    }
+}
 
-        Ok(desc)
+impl WaterMaskPipeline {
+    /// Whether the mask pass draws, so that the passes reading its output
+    /// don't read the mask of an earlier frame, or an uninitialized one.
+    pub(crate) fn is_ready(&self, pipeline_cache: &PipelineCache) -> bool {
+        pipeline_cache.get_render_pipeline(self.cached).is_some()
     }
 }
 
-/// Render graph node for producing stencils from meshes.
+/// Render graph node producing the water mask from the rendered water sprites,
+/// thresholded and downsampled to the resolution of the jump flood.
 pub struct WaterMaskNode {
-    query: QueryState<&'static RenderPhase<WaterMask>>,
+    query: QueryState<&'static ViewWaterEffectResources>,
 }
 
 impl WaterMaskNode {
     pub const IN_VIEW: &'static str = "view";
 
-    /// The produced stencil buffer.
+    /// The produced water mask.
     ///
-    /// This has format `TextureFormat::Depth24PlusStencil8`. Fragments covered
-    /// by a mesh are assigned a value of 255. All other fragments are assigned
-    /// a value of 0. The depth aspect is unused.
-    pub const OUT_MASK: &'static str = "stencil";
+    /// This has format `TextureFormat::R8Unorm` and holds the land coverage
+    /// of each fragment, as chosen by [`MaskMode`](crate::MaskMode).
+    pub const OUT_MASK: &'static str = "mask";
+}
 
-    pub fn new(world: &mut World) -> WaterMaskNode {
+impl FromWorld for WaterMaskNode {
+    fn from_world(world: &mut World) -> Self {
         WaterMaskNode {
-            query: QueryState::new(world),
+            query: QueryState::from_world(world),
         }
     }
 }
@@ -242,28 +110,34 @@ impl Node for WaterMaskNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), bevy::render::render_graph::NodeRunError> {
-        let res = world.get_resource::<WaterEffectResources>().unwrap();
+        let _span = bevy::log::info_span!("mask").entered();
 
-        graph
-            .set_output(Self::OUT_MASK, res.mask_multisample.default_view.clone())
-            .unwrap();
+        let view_entity = graph.get_input_entity(Self::IN_VIEW)?;
 
-        let view_entity = graph.get_input_entity(Self::IN_VIEW).unwrap();
+        // The driver only runs the water effect for views with resources.
+        let res = match self.query.get_manual(world, view_entity) {
+            Ok(res) => res,
+            Err(_) => return Ok(()),
+        };
 
-        dbg!(&view_entity);
+        graph.set_output(Self::OUT_MASK, res.mask_output.default_view.clone())?;
 
-        let stencil_phase = match self.query.get_manual(world, view_entity) {
-            Ok(q) => q,
-            Err(_) => return Ok(()),
+        let pipeline = world.resource::<WaterMaskPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipeline.cached) {
+            Some(c) => c,
+            // Still queued.
+            None => return Ok(()),
         };
 
-        let pass_raw = render_context
+        let render_pass = render_context
             .command_encoder
             .begin_render_pass(&RenderPassDescriptor {
-                label: Some("water_effect_stencil_render_pass"),
+                label: Some("water_effect_mask"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &res.mask_multisample.default_view,
-                    resolve_target: Some(&res.mask_output.default_view), // TODO: might want to remove for debugging
+                    view: &res.mask_output.default_view,
+                    resolve_target: None,
                     ops: Operations {
                         load: LoadOp::Clear(Color::BLACK.into()),
                         store: true,
@@ -272,22 +146,10 @@ impl Node for WaterMaskNode {
                 depth_stencil_attachment: None,
             });
 
-        // dbg!(&pass_raw);
-
-        let mut pass = TrackedRenderPass::new(pass_raw);
-
-        let draw_functions = world
-            .get_resource::<DrawFunctions<WaterMask>>()
-            .unwrap();
-        let mut draw_functions = draw_functions.write();
-
-        for item in stencil_phase.items.iter() {
-
-            dbg!(&item);
-
-            let draw_function = draw_functions.get_mut(item.draw_function()).unwrap();
-            draw_function.draw(world, &mut pass, view_entity, item);
-        }
+        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        tracked_pass.set_render_pipeline(cached_pipeline);
+        tracked_pass.set_bind_group(0, &res.mask_src_bind_group, &[]);
+        tracked_pass.draw(0..3, 0..1);
 
         Ok(())
     }
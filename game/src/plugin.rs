@@ -2,40 +2,45 @@ use bevy::prelude::*;
 use bevy::render::RenderApp;
 use bevy::render::render_resource::*;
 use bevy::render::RenderStage;
-use bevy::render::render_phase::DrawFunctions;
-use bevy::render::render_asset::RenderAssets;
-use bevy::sprite::Mesh2dHandle;
-use bevy::sprite::Mesh2dUniform;
-use bevy::render::view::ExtractedView;
-use bevy::render::view::VisibleEntities;
-use bevy::render::render_phase::RenderPhase;
-use bevy::sprite::Mesh2dPipelineKey;
-use bevy::render::render_phase::AddRenderCommand;
-use bevy::sprite::Material2dPlugin;
-use bevy::render::render_graph::RenderGraph;
-use bevy::render::render_asset::RenderAssetPlugin;
-use bevy::render::render_phase::SetItemPipeline;
+use bevy::render::camera::CameraUpdateSystem;
+use bevy::transform::TransformSystem;
+use bevy::render::render_asset::{PrepareAssetLabel, RenderAssetPlugin};
 use bevy::render::extract_component::ExtractComponentPlugin;
-use bevy::render::extract_resource::ExtractResourcePlugin;
 use bevy::render::renderer::RenderQueue;
 use bevy::render::Extract;
 use bevy::reflect::TypeUuid;
 use bevy::asset::load_internal_asset;
 
 use crate::components::WaterEffectImages;
-use crate::components::WaterSpritesMaterial;
-use crate::ripples_style::RipplesStyle;
-use crate::mask::WaterMask;
-use crate::mask::DrawWaterMask;
+use crate::components::{MainCamera, RipplesCamera, RipplesTexture, WaterSource, WaterSpritesCamera};
+use crate::rig::{rebuild_water_effect_images, WaterEffectRig};
+use crate::style_transition::{
+    advance_ripples_style_transitions, extract_ripples_style_transitions,
+    prepare_ripples_style_transitions, BlendedRipplesParams, Easing, RipplesStyleTransition,
+};
+use crate::ripple_impulse::{
+    collect_ripple_impulses, extract_ripple_impulses, prepare_ripple_impulses,
+    ActiveRippleImpulses, RippleImpulse,
+};
+use crate::ripples_style::{GradientStop, RipplesStyle, RipplesStyleLoader};
 use crate::resources;
 use crate::mask::WaterMaskPipeline;
 use crate::jfa_init::JfaInitPipeline;
 use crate::jfa::JfaPipeline;
+use crate::jfa_compute::JfaComputePipelines;
 use crate::ripples::RipplesPipeline;
 use crate::graph;
-use crate::components::WaterSpritesToTexture;
-use crate::components::RipplesCamera;
-use crate::components::ExtractedTime;
+use crate::components::{extract_time, ExtractedTime};
+use crate::components::{follow_main_camera, resize_water_effect_images};
+use crate::components::{add_water_source_render_layers, remove_water_source_render_layers};
+use crate::error::{
+    next_water_effect_error_frame, send_water_effect_errors, ErrorReporter, WaterEffectError,
+};
+use crate::settings::{JfaBackend, WaterEffectSettings};
+use crate::status::{
+    sync_water_effect_status, track_pipelines, PipelineTracker, StatusMirror, WaterEffectReady,
+    WaterEffectStatus,
+};
 // use crate::components::RipplesMaterial;
 
 const FULLSCREEN_SHADER_HANDLE: HandleUntyped =
@@ -43,7 +48,19 @@ const FULLSCREEN_SHADER_HANDLE: HandleUntyped =
 const DIMENSIONS_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 11721531257850828867);
 
-pub struct WaterEffectPlugin;
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct WaterSourceStage;
+
+#[derive(Default)]
+pub struct WaterEffectPlugin {
+    settings: WaterEffectSettings,
+}
+
+impl WaterEffectPlugin {
+    pub fn new(settings: WaterEffectSettings) -> Self {
+        Self { settings }
+    }
+}
 
 impl Plugin for WaterEffectPlugin {
     fn build(&self, app: &mut App) {
@@ -61,15 +78,65 @@ impl Plugin for WaterEffectPlugin {
             Shader::from_wgsl
         );
 
+        if self.settings.jfa_texture_format_overridden() {
+            warn!(
+                "jfa_texture_format {:?} is ignored by the compute backend, which uses {:?}",
+                self.settings.jfa_texture_format,
+                self.settings.jfa_format()
+            );
+        }
+
+        let error_reporter = ErrorReporter::default();
+        let status_mirror = StatusMirror::default();
+
         app
+            .insert_resource(self.settings.clone())
+            .insert_resource(error_reporter.clone())
+            .insert_resource(status_mirror.clone())
+            .init_resource::<WaterEffectStatus>()
+            .register_type::<MainCamera>()
+            .register_type::<WaterSpritesCamera>()
+            .register_type::<RipplesCamera>()
+            .register_type::<RipplesTexture>()
+            .register_type::<WaterSource>()
+            .register_type::<WaterEffectRig>()
+            .register_type::<RipplesStyle>()
+            .register_type::<GradientStop>()
+            .register_type::<Handle<RipplesStyle>>()
+            .register_type::<RipplesStyleTransition>()
+            .register_type::<Easing>()
+            .add_event::<WaterEffectError>()
+            .add_event::<WaterEffectReady>()
+            .add_event::<RippleImpulse>()
+            .init_resource::<ActiveRippleImpulses>()
+            .add_system_to_stage(CoreStage::First, send_water_effect_errors)
+            .add_system_to_stage(CoreStage::First, sync_water_effect_status)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                resize_water_effect_images.before(CameraUpdateSystem),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, advance_ripples_style_transitions)
+            .add_system_to_stage(CoreStage::PostUpdate, collect_ripple_impulses)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                follow_main_camera
+                    .before(CameraUpdateSystem)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            // Own stage, so that the layers inserted for sources spawned
+            // during the update, and the images of the rigs loaded from a
+            // scene, are in place before visibility is computed and the
+            // images are resized.
+            .add_stage_after(CoreStage::Update, WaterSourceStage, SystemStage::parallel())
+            .add_system_to_stage(WaterSourceStage, rebuild_water_effect_images)
+            .add_system_to_stage(WaterSourceStage, add_water_source_render_layers)
+            .add_system_to_stage(WaterSourceStage, remove_water_source_render_layers)
             .add_plugin(ExtractComponentPlugin::<RipplesCamera>::default())
-            .add_plugin(ExtractComponentPlugin::<WaterSpritesToTexture>::default()) // TODO: is this necessary?
-            .add_plugin(ExtractResourcePlugin::<ExtractedTime>::default())
-            .add_plugin(Material2dPlugin::<WaterSpritesMaterial>::default())
+            .add_plugin(ExtractComponentPlugin::<WaterEffectImages>::default())
             // .add_plugin(Material2dPlugin::<RipplesMaterial>::default())
             .add_plugin(RenderAssetPlugin::<RipplesStyle>::default())
             .add_asset::<RipplesStyle>()
-            .init_resource::<WaterEffectImages>();
+            .init_asset_loader::<RipplesStyleLoader>();
 
     
         let render_app = match app.get_sub_app_mut(RenderApp) {
@@ -77,52 +144,55 @@ impl Plugin for WaterEffectPlugin {
             Err(_) => return,
         };
 
-        let water_effect_driver_node = graph::WaterEffectDriverNode::new(&mut render_app.world);
-
         render_app
-            .init_resource::<DrawFunctions<WaterMask>>()
-            .add_render_command::<WaterMask, SetItemPipeline>()
-            .add_render_command::<WaterMask, DrawWaterMask>()
+            .insert_resource(self.settings.clone())
+            .insert_resource(error_reporter.clone())
+            .insert_resource(status_mirror)
+            .init_resource::<PipelineTracker>()
+            .init_resource::<BlendedRipplesParams>()
             .init_resource::<resources::WaterEffectResources>()
             .init_resource::<WaterMaskPipeline>()
-            .init_resource::<SpecializedMeshPipelines<WaterMaskPipeline>>()
-            .init_resource::<JfaInitPipeline>()
-            .init_resource::<JfaPipeline>()
             .init_resource::<RipplesPipeline>()
             .init_resource::<SpecializedRenderPipelines<RipplesPipeline>>()
+            .add_system_to_stage(RenderStage::Extract, next_water_effect_error_frame)
+            .add_system_to_stage(RenderStage::Extract, extract_time)
             .add_system_to_stage(RenderStage::Extract, extract_ripples_styles)
-            .add_system_to_stage(RenderStage::Extract, extract_ripples_camera_and_add_water_mask_phase)
+            .add_system_to_stage(RenderStage::Extract, extract_ripples_style_transitions)
+            .add_system_to_stage(RenderStage::Extract, extract_ripple_impulses)
             .add_system_to_stage(RenderStage::Prepare, prepare_time)
-            .add_system_to_stage(RenderStage::Prepare,resources::recreate)
-            .add_system_to_stage(RenderStage::Queue, queue_water_mask);
-
-        let water_effect_subgraph = graph::water_effect(render_app).unwrap();
-
-        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
-        let draw_2d_graph = root_graph.get_sub_graph_mut(bevy::core_pipeline::core_2d::graph::NAME).unwrap();
-        let draw_2d_input = draw_2d_graph.input_node().unwrap().id;
-
-        draw_2d_graph.add_sub_graph(graph::water_effect::NAME, water_effect_subgraph);
-        let water_effect_driver =
-            draw_2d_graph.add_node(graph::WaterEffectDriverNode::NAME, water_effect_driver_node);
-        draw_2d_graph
-            .add_slot_edge(
-                draw_2d_input,
-                bevy::core_pipeline::core_2d::graph::input::VIEW_ENTITY,
-                water_effect_driver,
-                graph::WaterEffectDriverNode::INPUT_VIEW,
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                prepare_ripples_style_transitions.after(PrepareAssetLabel::AssetPrepare),
             )
-            .unwrap();
-        draw_2d_graph
-            .add_node_edge(bevy::core_pipeline::core_2d::graph::node::MAIN_PASS, water_effect_driver)
-            .unwrap();
+            .add_system_to_stage(RenderStage::Prepare, resources::prepare_view_resources)
+            .add_system_to_stage(RenderStage::Prepare, prepare_ripple_impulses)
+            .add_system_to_stage(RenderStage::Cleanup, track_pipelines);
+
+        match self.settings.jfa_backend {
+            JfaBackend::RenderPass => {
+                render_app
+                    .init_resource::<JfaInitPipeline>()
+                    .init_resource::<JfaPipeline>();
+            }
+            JfaBackend::Compute => {
+                render_app.init_resource::<JfaComputePipelines>();
+            }
+        }
+
+        if let Err(err) = graph::add_to_core_2d(render_app) {
+            error_reporter.report(err.clone());
+            render_app.world.resource_mut::<PipelineTracker>().fail(err);
+        }
     }
 }
 
+type RipplesCameraStyles<'w, 's> =
+    Query<'w, 's, (Entity, &'static Handle<RipplesStyle>), With<RipplesCamera>>;
+
 fn extract_ripples_styles(
     mut commands: Commands,
     mut previous_ripples_styles_len: Local<usize>,
-    ripples_camera: Extract<Query<(Entity, &Handle<RipplesStyle>), With<RipplesCamera>>>,
+    ripples_camera: Extract<RipplesCameraStyles>,
 ) {
     let mut batches = Vec::with_capacity(*previous_ripples_styles_len);
     batches.extend(
@@ -132,25 +202,9 @@ fn extract_ripples_styles(
     );
     *previous_ripples_styles_len = batches.len();
 
-    //dbg!(&batches);
-
     commands.insert_or_spawn_batch(batches);
 }
 
-fn extract_ripples_camera_and_add_water_mask_phase(
-    mut commands: Commands,
-    cameras: Extract<Query<Entity, With<RipplesCamera>>>,
-) {
-    for entity in cameras.iter() {
-
-        dbg!(&entity);
-
-        commands
-            .get_or_spawn(entity)
-            .insert(RenderPhase::<WaterMask>::default());
-    }
-}
-
 fn prepare_time(
     time: Res<ExtractedTime>,
     water_effect_resources: Res<resources::WaterEffectResources>,
@@ -162,85 +216,3 @@ fn prepare_time(
         bevy::core::cast_slice(&[time.seconds_since_startup]),
     );
 }
-
-fn queue_water_mask(
-    water_mask_draw_function: Res<DrawFunctions<WaterMask>>,
-    mesh_mask_pipeline: Res<WaterMaskPipeline>,
-    mut pipelines: ResMut<SpecializedMeshPipelines<WaterMaskPipeline>>,
-    mut pipeline_cache: ResMut<PipelineCache>,
-    render_meshes: Res<RenderAssets<Mesh>>,
-    water_sprites_mesh: Query<(Entity, &Mesh2dHandle, &Mesh2dUniform), With<WaterSpritesToTexture>>,
-    mut views: Query<(
-        &ExtractedView,
-        &mut VisibleEntities,
-        &mut RenderPhase<WaterMask>,
-    )>,
-) {
-    let draw_water_mask = water_mask_draw_function
-        .read()
-        .get_id::<DrawWaterMask>()
-        .unwrap();
-
-    for (view, visible_entities, mut mesh_mask_phase) in views.iter_mut() {
-
-        // TODO: these were 1600 and 900 and they were a bit weird honestly, why?
-        // TODO: now they are 1280, 720, which i think make more sense?
-        // dbg!(&view.width);
-        // dbg!(&view.height);
-
-        // NOTE: ok, with render layers this works, it only sees the one texture it's supposed to see
-        //dbg!(&visible_entities);
-
-        // let view_matrix = view.transform.compute_matrix();
-        // let inv_view_row_2 = view_matrix.inverse().row(2);
-
-        for visible_entity in visible_entities.entities.iter().copied() {
-
-            let (entity, mesh2d_handle, mesh2d_uniform) = match water_sprites_mesh.get(visible_entity) {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
-
-            dbg!(&visible_entity);
-            //dbg!(&mesh2d_handle);
-
-            let mesh = match render_meshes.get(&mesh2d_handle.0) {
-                Some(m) => m,
-                None => continue,
-            };
-
-            let key = Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology);
-
-            // dbg!(&key);
-
-            let pipeline = pipelines
-                .specialize(&mut pipeline_cache, &mesh_mask_pipeline, key, &mesh.layout)
-                .unwrap();
-
-            let mesh_z = mesh2d_uniform.transform.w_axis.z;
-
-            //dbg!(&mesh_z);
-
-            let pipeline_state = pipeline_cache.get_render_pipeline_state(pipeline);
-            //dbg!(&pipeline_state);
-
-            match pipeline_state {
-                CachedPipelineState::Ok(_) => {
-
-                    bevy::log::info!("water mask pipeline state is Ok");
-
-                    mesh_mask_phase.add(WaterMask {
-                        entity,
-                        pipeline,
-                        draw_function: draw_water_mask,
-                        distance: mesh_z,
-                    });
-                }
-                _ => {
-                    bevy::log::warn!("water mask pipeline state is not Ok");
-                    dbg!(&pipeline_state);
-                },
-            }
-        }
-    }
-}
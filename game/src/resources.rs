@@ -1,38 +1,42 @@
 use bevy::{
     prelude::*,
     render::{
+        camera::ExtractedCamera,
+        render_asset::RenderAssets,
         render_resource::*,
         renderer::{RenderDevice, RenderQueue},
-        texture::{CachedTexture, TextureCache},
-        view::ExtractedWindows,
+        texture::{CachedTexture, GpuImage, TextureCache},
     },
-    window::WindowId,
+    utils::HashMap,
 };
 
-use crate::{jfa, 
-    JFA_TEXTURE_FORMAT, 
-    ripples_style};
+use crate::{
+    components::{RipplesCamera, WaterEffectImages},
+    jfa,
+    jfa_compute::{JfaComputePipelines, ViewJfaComputeBindGroups},
+    ripples_style,
+    settings::WaterEffectSettings,
+    uniforms::RippleImpulses,
+};
 
 const JFA_FROM_PRIMARY: &str = "jfa_from_primary_output_bind_group";
 const JFA_FROM_SECONDARY: &str = "jfa_from_secondary_output_bind_group";
 const JFA_RIPPLES_SRC: &str = "jfa_ripples_src_bind_group";
 
+/// Layouts and buffers shared by every water effect view.
 pub struct WaterEffectResources {
-    // Multisample target for initial mask pass.
-    pub mask_multisample: CachedTexture,
-    // Resolve target for the above.
-    pub mask_output: CachedTexture,
-
     pub dimensions_bind_group_layout: BindGroupLayout,
-    pub dimensions_buffer: UniformBuffer<jfa::Dimensions>,
-    pub dimensions_bind_group: BindGroup,
 
-    // Non-filtering sampler for all sampling operations.
+    // Non-filtering sampler for all sampling operations but the mask pass.
     pub sampler: Sampler,
 
-    // Bind group and layout for JFA init pass.
+    // Bind group layout and filtering sampler for reading the rendered water
+    // sprites in the mask pass.
+    pub mask_src_bind_group_layout: BindGroupLayout,
+    pub mask_src_sampler: Sampler,
+
+    // Bind group layout for JFA init pass.
     pub jfa_init_bind_group_layout: BindGroupLayout,
-    pub jfa_init_bind_group: BindGroup,
 
     // Bind group layout for JFA iteration passes.
     pub jfa_bind_group_layout: BindGroupLayout,
@@ -41,29 +45,108 @@ pub struct WaterEffectResources {
     pub jfa_distance_buffer: DynamicUniformBuffer<jfa::JumpDist>,
     pub jfa_distance_offsets: Vec<u32>,
 
+    // Bind group layout for sampling JFA results in the ripples shader.
+    pub ripples_src_bind_group_layout: BindGroupLayout,
+    // Bind group layout for ripples style parameters and water gradient, and
+    // the filtering sampler for the gradient.
+    pub ripples_params_bind_group_layout: BindGroupLayout,
+    pub ripples_gradient_sampler: Sampler,
+
+    // Bind group layout for the ripples time uniform and the ripple impulses
+    // of a view, and the buffer for the time.
+    pub ripples_time_bind_group_layout: BindGroupLayout,
+    pub ripples_time_uniform_buffer: Buffer,
+}
+
+/// Textures and bind groups of a single water effect view, sized from the
+/// render target of its camera.
+///
+/// Inserted on every extracted `RipplesCamera` view by [`prepare_view_resources`].
+#[derive(Clone, Component)]
+pub struct ViewWaterEffectResources {
+    // Bind group for the mask pass, sampling the rendered water sprites.
+    pub mask_src_bind_group: BindGroup,
+    // Output of the mask pass, at the resolution of the jump flood it seeds.
+    pub mask_output: CachedTexture,
+
+    pub dimensions: jfa::Dimensions,
+    pub dimensions_bind_group: BindGroup,
+
+    // Dimensions of the jump flood textures, used by the JFA init and JFA passes.
+    pub jfa_dimensions: jfa::Dimensions,
+    pub jfa_dimensions_bind_group: BindGroup,
+
+    // Bind group for JFA init passes.
+    pub jfa_init_bind_group: BindGroup,
+
+    // Jump flood from the land, i.e. the distance to the coast over water.
+    pub jfa: JfaFlood,
+    // Jump flood from the water over the inverted mask, only there with
+    // `WaterEffectSettings::distance_field_output`.
+    pub inverted_jfa: Option<JfaFlood>,
+
+    pub ripples_src_bind_group: BindGroup,
+
+    // Bind groups of the compute jump flood, only there with
+    // `JfaBackend::Compute`.
+    pub(crate) jfa_compute: Option<ViewJfaComputeBindGroups>,
+
+    // Rendered water sprites the mask bind group was created for.
+    mask_src_texture: TextureId,
+}
+
+/// Textures and bind groups of one jump flood, which ping-pongs between the
+/// primary and secondary outputs before writing the final one.
+#[derive(Clone)]
+pub struct JfaFlood {
     // Bind group for jump flood passes targeting the primary output.
-    pub jfa_from_secondary_bind_group: BindGroup,
-    // Primary jump flood output.
-    pub jfa_primary_output: CachedTexture,
+    pub from_secondary_bind_group: BindGroup,
+    // Primary jump flood output, also written by the JFA init pass.
+    pub primary_output: CachedTexture,
 
     // Bind group for jump flood passes targeting the secondary output.
-    pub jfa_from_primary_bind_group: BindGroup,
+    pub from_primary_bind_group: BindGroup,
     // Secondary jump flood output.
-    pub jfa_secondary_output: CachedTexture,
+    pub secondary_output: CachedTexture,
 
-    // Bind groups for the final jump flood pass.
-    pub jfa_final_output: CachedTexture,
+    // Output of the final jump flood pass.
+    pub final_output: CachedTexture,
+}
 
-    // Bind group layout for sampling JFA results in the ripples shader.
-    pub ripples_src_bind_group_layout: BindGroupLayout,
-    // Bind group layout for ripples style parameters.
-    pub ripples_params_bind_group_layout: BindGroupLayout,
-    pub ripples_src_bind_group: BindGroup,
+impl JfaFlood {
+    fn texture_ids(&self) -> [TextureId; 3] {
+        [
+            self.primary_output.texture.id(),
+            self.secondary_output.texture.id(),
+            self.final_output.texture.id(),
+        ]
+    }
+}
 
-    // Bind group layout, bind group and buffer for ripples time uniform.
-    pub ripples_time_bind_group_layout: BindGroupLayout,
-    pub ripples_time_bind_group: BindGroup,
-    pub ripples_time_uniform_buffer: Buffer,
+/// Textures of a [`JfaFlood`], before its bind groups are created.
+struct JfaTextures {
+    primary_output: CachedTexture,
+    secondary_output: CachedTexture,
+    final_output: CachedTexture,
+}
+
+impl JfaTextures {
+    fn texture_ids(&self) -> [TextureId; 3] {
+        [
+            self.primary_output.texture.id(),
+            self.secondary_output.texture.id(),
+            self.final_output.texture.id(),
+        ]
+    }
+}
+
+impl ViewWaterEffectResources {
+    fn texture_ids(&self) -> Vec<TextureId> {
+        let mut ids = vec![self.mask_src_texture, self.mask_output.texture.id()];
+        ids.extend(self.jfa.texture_ids());
+        ids.extend(self.inverted_jfa.iter().flat_map(JfaFlood::texture_ids));
+        ids
+    }
 }
 
 impl WaterEffectResources {
@@ -89,6 +172,7 @@ impl WaterEffectResources {
         label: &str,
         src: &TextureView,
         mask: &TextureView,
+        inverted_src: &TextureView,
         sampler: &Sampler,
     ) -> BindGroup {
         device.create_bind_group(&BindGroupDescriptor {
@@ -107,25 +191,14 @@ impl WaterEffectResources {
                     binding: 2,
                     resource: BindingResource::Sampler(sampler),
                 },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(inverted_src),
+                },
             ],
         })
     }
 
-    fn create_time_uniform_bind_group(
-        device: &RenderDevice,
-        time_bind_group_layout: &BindGroupLayout,
-        time_uniform_buffer: &Buffer
-    ) -> BindGroup {
-        device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: time_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: time_uniform_buffer.as_entire_binding(),
-            }],
-        })
-    }
-
     fn create_bind_group(
         device: &RenderDevice,
         layout: &BindGroupLayout,
@@ -154,7 +227,71 @@ impl WaterEffectResources {
         })
     }
 
-    fn tex_desc(label: &'static str, size: Extent3d, format: TextureFormat) -> TextureDescriptor {
+    fn create_dimensions_bind_group(
+        &self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        label: &str,
+        dimensions: jfa::Dimensions,
+    ) -> BindGroup {
+        let mut buffer = UniformBuffer::from(dimensions);
+        buffer.write_buffer(device, queue);
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.dimensions_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.binding().unwrap(),
+            }],
+        })
+    }
+
+    /// Takes the primary, secondary and final textures of a jump flood from
+    /// the texture cache, labelled with `labels` in that order.
+    fn take_jfa_textures(
+        device: &RenderDevice,
+        textures: &mut TextureCache,
+        settings: &WaterEffectSettings,
+        size: Extent3d,
+        labels: [&'static str; 3],
+    ) -> JfaTextures {
+        let [primary_output, secondary_output, final_output] = labels.map(|label| {
+            let desc = TextureDescriptor {
+                usage: settings.jfa_usages(),
+                ..Self::tex_desc(label, size, settings.jfa_format())
+            };
+            textures.get(device, desc)
+        });
+        JfaTextures {
+            primary_output,
+            secondary_output,
+            final_output,
+        }
+    }
+
+    fn create_jfa_flood(&self, device: &RenderDevice, textures: JfaTextures) -> JfaFlood {
+        let from_primary_bind_group = self.create_jfa_pass_bind_group(
+            device,
+            JFA_FROM_PRIMARY,
+            &textures.primary_output.default_view,
+        );
+        let from_secondary_bind_group = self.create_jfa_pass_bind_group(
+            device,
+            JFA_FROM_SECONDARY,
+            &textures.secondary_output.default_view,
+        );
+
+        JfaFlood {
+            from_secondary_bind_group,
+            primary_output: textures.primary_output,
+            from_primary_bind_group,
+            secondary_output: textures.secondary_output,
+            final_output: textures.final_output,
+        }
+    }
+
+    fn tex_desc(label: &'static str, size: Extent3d, format: TextureFormat) -> TextureDescriptor<'static> {
         TextureDescriptor {
             label: Some(label),
             size,
@@ -165,39 +302,159 @@ impl WaterEffectResources {
             usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
         }
     }
-}
 
-impl FromWorld for WaterEffectResources {
-    fn from_world(world: &mut World) -> Self {
-        let size = Extent3d {
-            width: 1,
-            height: 1,
+    /// Takes the textures of a view of `size` from the texture cache, and
+    /// creates its buffers and bind groups, including the compute ones with
+    /// `jfa_compute`, unless `previous` already uses the same textures.
+    #[allow(clippy::too_many_arguments)]
+    fn prepare_view(
+        &self,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        textures: &mut TextureCache,
+        settings: &WaterEffectSettings,
+        size: Extent3d,
+        water_sprites: &GpuImage,
+        jfa_compute: Option<&JfaComputePipelines>,
+        previous: Option<ViewWaterEffectResources>,
+    ) -> ViewWaterEffectResources {
+        let jfa_scale = settings
+            .jfa_resolution_scale
+            .clamp(WaterEffectSettings::MIN_JFA_RESOLUTION_SCALE, 1.0);
+        let jfa_size = Extent3d {
+            width: ((size.width as f32 * jfa_scale).ceil() as u32).max(1),
+            height: ((size.height as f32 * jfa_scale).ceil() as u32).max(1),
             depth_or_array_layers: 1,
         };
 
-        let device = world.get_resource::<RenderDevice>().unwrap().clone();
-        let queue = world.get_resource::<RenderQueue>().unwrap().clone();
-        let mut textures = world.get_resource_mut::<TextureCache>().unwrap();
-
-        let mask_output_desc = Self::tex_desc("water_effect_mask_output", size, TextureFormat::R8Unorm);
-        let mask_multisample_desc = TextureDescriptor {
-            label: Some("water_effect_mask_multisample"),
-            sample_count: 4,
-            ..mask_output_desc.clone()
+        let mask_output_desc = TextureDescriptor {
+            // COPY_SRC for headless readback.
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+            // Downsampled through the filtering sampler of the mask pass, so
+            // that the 3x3 neighbourhood of the init pass is adjacent texels.
+            ..Self::tex_desc("water_effect_mask_output", jfa_size, TextureFormat::R8Unorm)
         };
-        let mask_multisample = textures.get(&device, mask_multisample_desc);
-        let mask_output = textures.get(&device, mask_output_desc);
+        let mask_output = textures.get(device, mask_output_desc);
 
-        let dims = jfa::Dimensions::new(size.width, size.height);
-        let mut dimensions_buffer = UniformBuffer::from(dims);
-        dimensions_buffer.write_buffer(&device, &queue);
+        let jfa = Self::take_jfa_textures(
+            device,
+            textures,
+            settings,
+            jfa_size,
+            [
+                "water_effect_jfa_primary_output",
+                "water_effect_jfa_secondary_output",
+                "water_effect_jfa_final_output",
+            ],
+        );
+        let inverted_jfa = settings.distance_field_output.then(|| {
+            Self::take_jfa_textures(
+                device,
+                textures,
+                settings,
+                jfa_size,
+                [
+                    "water_effect_inverted_jfa_primary_output",
+                    "water_effect_inverted_jfa_secondary_output",
+                    "water_effect_inverted_jfa_final_output",
+                ],
+            )
+        });
+
+        let mut texture_ids = vec![water_sprites.texture.id(), mask_output.texture.id()];
+        texture_ids.extend(jfa.texture_ids());
+        texture_ids.extend(inverted_jfa.iter().flat_map(JfaTextures::texture_ids));
+        if let Some(previous) = previous {
+            if previous.texture_ids() == texture_ids {
+                return previous;
+            }
+        }
+
+        let dimensions = jfa::Dimensions::new(size.width, size.height);
+        let dimensions_bind_group =
+            self.create_dimensions_bind_group(device, queue, "water_effect_dimensions_bind_group", dimensions);
+        let jfa_dimensions = jfa::Dimensions::new(jfa_size.width, jfa_size.height);
+        let jfa_dimensions_bind_group =
+            self.create_dimensions_bind_group(device, queue, "jfa_dimensions_bind_group", jfa_dimensions);
+
+        let mask_src_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("water_effect_mask_src_bind_group"),
+            layout: &self.mask_src_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&water_sprites.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.mask_src_sampler),
+                },
+            ],
+        });
+
+        let jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("water_effect_jfa_init_bind_group"),
+            layout: &self.jfa_init_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&mask_output.default_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let jfa = self.create_jfa_flood(device, jfa);
+        let inverted_jfa = inverted_jfa.map(|textures| self.create_jfa_flood(device, textures));
+
+        // Without the inverted flood, the ripples shader does not read its
+        // binding, so any texture of the right format does.
+        let inverted_src = inverted_jfa.as_ref().unwrap_or(&jfa);
+        let ripples_src_bind_group = Self::create_ripples_src_bind_group(
+            device,
+            &self.ripples_src_bind_group_layout,
+            JFA_RIPPLES_SRC,
+            &jfa.final_output.default_view,
+            &mask_output.default_view,
+            &inverted_src.final_output.default_view,
+            &self.sampler,
+        );
+
+        let jfa_compute = jfa_compute.map(|pipelines| {
+            ViewJfaComputeBindGroups::new(device, pipelines, self, &mask_output, &jfa, inverted_jfa.as_ref())
+        });
+
+        ViewWaterEffectResources {
+            mask_src_bind_group,
+            mask_output,
+            dimensions,
+            dimensions_bind_group,
+            jfa_dimensions,
+            jfa_dimensions_bind_group,
+            jfa_init_bind_group,
+            jfa,
+            inverted_jfa,
+            ripples_src_bind_group,
+            jfa_compute,
+            mask_src_texture: water_sprites.texture.id(),
+        }
+    }
+}
+
+impl FromWorld for WaterEffectResources {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.get_resource::<RenderDevice>().unwrap().clone();
+        let queue = world.get_resource::<RenderQueue>().unwrap().clone();
 
         let dimensions_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("jfa_dimensions_bind_group_layout"),
                 entries: &[BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
+                    visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -207,15 +464,6 @@ impl FromWorld for WaterEffectResources {
                 }],
             });
 
-        let dimensions_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("jfa_dimensions_bind_group"),
-            layout: &dimensions_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: dimensions_buffer.binding().unwrap(),
-            }],
-        });
-
         let sampler = device.create_sampler(&SamplerDescriptor {
             label: Some("water_effect_jfa_sampler"),
             address_mode_u: AddressMode::ClampToEdge,
@@ -228,6 +476,40 @@ impl FromWorld for WaterEffectResources {
             ..Default::default()
         });
 
+        let mask_src_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("water_effect_mask_src_bind_group_layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let mask_src_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("water_effect_mask_src_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
         let jfa_init_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("water_effect_jfa_init_bind_group_layout"),
@@ -251,21 +533,6 @@ impl FromWorld for WaterEffectResources {
                 ],
             });
 
-        let jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("water_effect_jfa_init_bind_group"),
-            layout: &jfa_init_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&mask_output.default_view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
-
         let jfa_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("water_effect_jfa_bind_group_layout"),
             entries: &[
@@ -309,40 +576,6 @@ impl FromWorld for WaterEffectResources {
         }
         jfa_distance_buffer.write_buffer(&device, &queue);
 
-        let jfa_primary_output_desc =
-            Self::tex_desc("water_effect_jfa_primary_output", size, JFA_TEXTURE_FORMAT);
-        let jfa_primary_output = textures.get(&device, jfa_primary_output_desc);
-        let jfa_secondary_output_desc = Self::tex_desc(
-            "water_effect_jfa_secondary_output",
-            size,
-            JFA_TEXTURE_FORMAT,
-        );
-        let jfa_secondary_output = textures.get(&device, jfa_secondary_output_desc);
-        let jfa_final_output_desc =
-            Self::tex_desc("water_effect_jfa_final_output", size, JFA_TEXTURE_FORMAT);
-        let jfa_final_output = textures.get(&device, jfa_final_output_desc);
-
-        let jfa_from_secondary_bind_group = Self::create_bind_group(
-            &device,
-            &jfa_bind_group_layout,
-            "water_effect_jfa_primary_bind_group",
-            jfa_distance_buffer.binding().unwrap(),
-            &jfa_secondary_output.default_view,
-            &sampler,
-        );
-        let jfa_from_primary_bind_group = Self::create_bind_group(
-            &device,
-            &jfa_bind_group_layout,
-            "water_effect_jfa_secondary_bind_group",
-            jfa_distance_buffer.binding().unwrap(),
-            &jfa_primary_output.default_view,
-            &sampler,
-        );
-
-        let mut ripples_params_buffer =
-            UniformBuffer::from(ripples_style::RipplesParams::default());
-        ripples_params_buffer.write_buffer(&device, &queue);
-
         let ripples_src_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("jfa_ripples_bind_group_layout"),
@@ -376,6 +609,17 @@ impl FromWorld for WaterEffectResources {
                         ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
                         count: None,
                     },
+                    // Inverted JFA texture
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: false },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -394,31 +638,62 @@ impl FromWorld for WaterEffectResources {
                         },
                         count: None,
                     },
+                    // Water gradient
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D1,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Gradient sampler
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             });
 
-        let ripples_src_bind_group = Self::create_ripples_src_bind_group(
-            &device,
-            &ripples_src_bind_group_layout,
-            "jfa_ripples_src_bind_group",
-            &jfa_final_output.default_view,
-            &mask_output.default_view,
-            &sampler,
-        );
+        let ripples_gradient_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("water_effect_ripples_gradient_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
 
         let ripples_time_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("jfa_ripples_time_bind_group_layout"),
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: BufferSize::new(std::mem::size_of::<f32>() as u64),
+                entries: &[
+                    // Time
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<f32>() as u64),
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    // Ripple impulses
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(RippleImpulses::min_size()),
+                        },
+                        count: None,
+                    },
+                ],
             });
 
         let ripples_time_uniform_buffer = device.create_buffer(&BufferDescriptor {
@@ -428,157 +703,74 @@ impl FromWorld for WaterEffectResources {
                 mapped_at_creation: false,
             });
 
-        // let ripples_time_bind_group = device.create_bind_group(&BindGroupDescriptor {
-        //     label: Some("jfa_ripples_time_bind_group"),
-        //     layout: &ripples_time_bind_group_layout,
-        //     entries: &[BindGroupEntry {
-        //         binding: 0,
-        //         resource: ripples_time_uniform_buffer.as_entire_binding(),
-        //     }],
-        // });
-
-        let ripples_time_bind_group = Self::create_time_uniform_bind_group(
-            &device, 
-            &ripples_time_bind_group_layout, 
-            &ripples_time_uniform_buffer
-        );
-
         WaterEffectResources {
-            mask_multisample,
-            mask_output,
             dimensions_bind_group_layout,
-            dimensions_buffer,
-            dimensions_bind_group,
             jfa_init_bind_group_layout,
-            jfa_init_bind_group,
             jfa_bind_group_layout,
             sampler,
+            mask_src_bind_group_layout,
+            mask_src_sampler,
             jfa_distance_buffer,
             jfa_distance_offsets,
-            jfa_primary_output,
-            jfa_secondary_output,
-            jfa_final_output,
-            jfa_from_secondary_bind_group,
-            jfa_from_primary_bind_group,
             ripples_src_bind_group_layout,
             ripples_params_bind_group_layout,
-            ripples_src_bind_group,
+            ripples_gradient_sampler,
             ripples_time_bind_group_layout,
-            ripples_time_bind_group,
             ripples_time_uniform_buffer,
         }
     }
 }
 
-pub fn recreate(
-    mut water_effect: ResMut<WaterEffectResources>,
+/// Inserts [`ViewWaterEffectResources`] on every extracted `RipplesCamera`,
+/// sized from its render target, once the water sprites of its
+/// [`WaterEffectImages`] are on the GPU.
+///
+/// Textures have to be taken from the cache every frame, but bind groups are
+/// only recreated when the cache hands out different textures, e.g. after a
+/// resize.
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_view_resources(
+    mut commands: Commands,
+    water_effect: Res<WaterEffectResources>,
     device: Res<RenderDevice>,
     queue: Res<RenderQueue>,
     mut textures: ResMut<TextureCache>,
-    windows: Res<ExtractedWindows>,
+    settings: Res<WaterEffectSettings>,
+    images: Res<RenderAssets<Image>>,
+    jfa_compute: Option<Res<JfaComputePipelines>>,
+    mut previous_views: Local<HashMap<Entity, ViewWaterEffectResources>>,
+    views: Query<(Entity, &ExtractedCamera, &WaterEffectImages), With<RipplesCamera>>,
 ) {
-    let primary = match windows.get(&WindowId::primary()) {
-        Some(w) => w,
-        None => return,
-    };
-
-    let size = Extent3d {
-        width: primary.physical_width,
-        height: primary.physical_height,
-        depth_or_array_layers: 1,
-    };
-
-    let jfa_size = size;
-
-    let new_dims = jfa::Dimensions::new(size.width, size.height);
-    let dims = water_effect.dimensions_buffer.get_mut();
-    if *dims != new_dims {
-        *dims = new_dims;
-        water_effect.dimensions_buffer.write_buffer(&device, &queue);
-    }
-
-    let old_mask = water_effect.mask_multisample.texture.id();
-    let mask_output_desc = WaterEffectResources::tex_desc("water_effect_mask_output", size, TextureFormat::R8Unorm);
-    let mask_multisample_desc = TextureDescriptor {
-        label: Some("water_effect_mask_multisample"),
-        sample_count: 4,
-        ..mask_output_desc.clone()
-    };
-
-    // Recreate mask output targets.
-    water_effect.mask_output = textures.get(&device, mask_output_desc);
-    water_effect.mask_multisample = textures.get(&device, mask_multisample_desc);
-
-    if water_effect.mask_output.texture.id() != old_mask {
-        // Recreate JFA init pass bind group
-        water_effect.jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("water_effect_jfa_init_bind_group"),
-            layout: &water_effect.jfa_init_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&water_effect.mask_output.default_view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&water_effect.sampler),
-                },
-            ],
-        });
-    }
+    let mut prepared_views = HashMap::default();
 
-    let old_jfa_primary = water_effect.jfa_primary_output.texture.id();
-    let jfa_primary_desc = WaterEffectResources::tex_desc(
-        "water_effect_jfa_primary_output",
-        jfa_size,
-        JFA_TEXTURE_FORMAT,
-    );
-    let jfa_primary_output = textures.get(&device, jfa_primary_desc);
-    if jfa_primary_output.texture.id() != old_jfa_primary {
-        water_effect.jfa_primary_output = jfa_primary_output;
-        water_effect.jfa_from_primary_bind_group = water_effect.create_jfa_pass_bind_group(
-            &device,
-            JFA_FROM_PRIMARY,
-            &water_effect.jfa_primary_output.default_view,
-        );
-    }
+    for (entity, camera, water_effect_images) in views.iter() {
+        let water_sprites = match images.get(&water_effect_images.rendered_water_sprites) {
+            Some(image) => image,
+            None => continue,
+        };
+        let target_size = match camera.physical_target_size {
+            Some(size) => size,
+            None => continue,
+        };
+        let size = Extent3d {
+            width: target_size.x,
+            height: target_size.y,
+            depth_or_array_layers: 1,
+        };
 
-    let old_jfa_secondary = water_effect.jfa_secondary_output.texture.id();
-    let jfa_secondary_desc = WaterEffectResources::tex_desc(
-        "water_effect_jfa_secondary_output",
-        jfa_size,
-        JFA_TEXTURE_FORMAT,
-    );
-    let jfa_secondary_output = textures.get(&device, jfa_secondary_desc);
-    if jfa_secondary_output.texture.id() != old_jfa_secondary {
-        water_effect.jfa_secondary_output = jfa_secondary_output;
-        water_effect.jfa_from_secondary_bind_group = water_effect.create_jfa_pass_bind_group(
+        let view = water_effect.prepare_view(
             &device,
-            JFA_FROM_SECONDARY,
-            &water_effect.jfa_secondary_output.default_view,
+            &queue,
+            &mut textures,
+            &settings,
+            size,
+            water_sprites,
+            jfa_compute.as_deref(),
+            previous_views.remove(&entity),
         );
+        commands.entity(entity).insert(view.clone());
+        prepared_views.insert(entity, view);
     }
 
-    let old_jfa_final = water_effect.jfa_final_output.texture.id();
-    let jfa_final_desc = WaterEffectResources::tex_desc("water_effect_jfa_final_output", size, JFA_TEXTURE_FORMAT);
-    let jfa_final_output = textures.get(&device, jfa_final_desc);
-    if jfa_final_output.texture.id() != old_jfa_final {
-        water_effect.jfa_final_output = jfa_final_output;
-        water_effect.ripples_src_bind_group = WaterEffectResources::create_ripples_src_bind_group(
-            &device,
-            &water_effect.ripples_src_bind_group_layout,
-            JFA_RIPPLES_SRC,
-            &water_effect.jfa_final_output.default_view,
-            &water_effect.mask_output.default_view,
-            &water_effect.sampler,
-        );
-
-        // TODO: i guess i need to recreate stuff here too?? 
-
-        water_effect.ripples_time_bind_group = WaterEffectResources::create_time_uniform_bind_group(
-            &device,
-            &water_effect.ripples_time_bind_group_layout,
-            &water_effect.ripples_time_uniform_buffer,
-        );
-    }
+    *previous_views = prepared_views;
 }
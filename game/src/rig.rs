@@ -0,0 +1,382 @@
+use bevy::ecs::entity::{EntityMap, MapEntities, MapEntitiesError};
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::ecs::system::Command;
+use bevy::prelude::*;
+use bevy::reflect::FromReflect;
+use bevy::render::camera::RenderTarget;
+
+use crate::components::{
+    MainCameraBundle, RipplesCameraBundle, RipplesTextureBundle, WaterEffectImages,
+    WaterSpritesCameraBundle,
+};
+use crate::ripples_style::RipplesStyle;
+
+/// Entities of a water effect spawned by
+/// [`spawn_water_effect`](WaterEffectCommandsExt::spawn_water_effect).
+///
+/// The cameras and the ripples sprite are children of `root`, which also
+/// holds a copy of this struct. The root stays at the origin: the water
+/// cameras and the sprite follow the main camera, not the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[reflect(Component, MapEntities, PartialEq)]
+pub struct WaterEffectRig {
+    pub root: Entity,
+    pub main_camera: Entity,
+    pub water_sprites_camera: Entity,
+    pub ripples_camera: Entity,
+    pub ripples_texture: Entity,
+}
+
+impl WaterEffectRig {
+    /// Despawns the root and everything below it.
+    pub fn despawn(&self, commands: &mut Commands) {
+        commands.entity(self.root).despawn_recursive();
+    }
+
+    fn entities_mut(&mut self) -> [&mut Entity; 5] {
+        [
+            &mut self.root,
+            &mut self.main_camera,
+            &mut self.water_sprites_camera,
+            &mut self.ripples_camera,
+            &mut self.ripples_texture,
+        ]
+    }
+}
+
+// Reflection needs a value to apply a scene onto, like for `Parent`.
+impl FromWorld for WaterEffectRig {
+    fn from_world(_world: &mut World) -> Self {
+        let placeholder = Entity::from_raw(u32::MAX);
+        WaterEffectRig {
+            root: placeholder,
+            main_camera: placeholder,
+            water_sprites_camera: placeholder,
+            ripples_camera: placeholder,
+            ripples_texture: placeholder,
+        }
+    }
+}
+
+impl MapEntities for WaterEffectRig {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for entity in self.entities_mut() {
+            *entity = entity_map.get(*entity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spawns the water effect from [`Commands`].
+pub trait WaterEffectCommandsExt {
+    /// Spawns the main camera, the water cameras and the sprite displaying
+    /// the ripples under one root entity, with the ripples drawn in `style`.
+    ///
+    /// The entities are reserved right away, and filled in when the commands
+    /// are applied, so they can be modified with commands in the meantime.
+    fn spawn_water_effect(&mut self, style: Handle<RipplesStyle>) -> WaterEffectRig;
+}
+
+impl<'w, 's> WaterEffectCommandsExt for Commands<'w, 's> {
+    fn spawn_water_effect(&mut self, style: Handle<RipplesStyle>) -> WaterEffectRig {
+        let rig = WaterEffectRig {
+            root: self.spawn().id(),
+            main_camera: self.spawn().id(),
+            water_sprites_camera: self.spawn().id(),
+            ripples_camera: self.spawn().id(),
+            ripples_texture: self.spawn().id(),
+        };
+        self.add(SpawnWaterEffect { rig, style });
+        rig
+    }
+}
+
+struct SpawnWaterEffect {
+    rig: WaterEffectRig,
+    style: Handle<RipplesStyle>,
+}
+
+impl Command for SpawnWaterEffect {
+    fn write(self, world: &mut World) {
+        let rig = self.rig;
+        let water_effect_images = WaterEffectImages::from_world(world);
+        let ripples_texture =
+            RipplesTextureBundle::new(world.resource::<Assets<Image>>(), &water_effect_images);
+
+        world.entity_mut(rig.main_camera).insert_bundle(MainCameraBundle::default());
+        world
+            .entity_mut(rig.water_sprites_camera)
+            .insert_bundle(WaterSpritesCameraBundle::new(&water_effect_images));
+        world
+            .entity_mut(rig.ripples_camera)
+            .insert_bundle(RipplesCameraBundle::with_style(self.style, &water_effect_images));
+        world.entity_mut(rig.ripples_texture).insert_bundle(ripples_texture);
+
+        world
+            .entity_mut(rig.root)
+            .insert_bundle(SpatialBundle::default())
+            .insert(rig)
+            .push_children(&[
+                rig.main_camera,
+                rig.water_sprites_camera,
+                rig.ripples_camera,
+                rig.ripples_texture,
+            ]);
+    }
+}
+
+/// Gives the rigs loaded from a scene images of their own to render into.
+///
+/// The images are created at runtime and are not part of the scene, so the
+/// loaded cameras and ripples sprite are pointed at new ones, which
+/// `resize_water_effect_images` then sizes like those of a spawned rig.
+pub(crate) fn rebuild_water_effect_images(
+    mut commands: Commands,
+    rigs: Query<&WaterEffectRig, Added<WaterEffectRig>>,
+    water_effect_images: Query<(), With<WaterEffectImages>>,
+) {
+    for rig in rigs.iter() {
+        if !water_effect_images.contains(rig.ripples_camera) {
+            commands.add(RebuildWaterEffectImages { rig: *rig });
+        }
+    }
+}
+
+struct RebuildWaterEffectImages {
+    rig: WaterEffectRig,
+}
+
+impl Command for RebuildWaterEffectImages {
+    fn write(self, world: &mut World) {
+        let rig = self.rig;
+        let water_effect_images = WaterEffectImages::from_world(world);
+        let quad_size = world
+            .resource::<Assets<Image>>()
+            .get(&water_effect_images.rendered_ripples)
+            .unwrap()
+            .size();
+
+        let mut ripples_texture = world.entity_mut(rig.ripples_texture);
+        if let Some(mut sprite) = ripples_texture.get_mut::<Sprite>() {
+            sprite.custom_size = Some(quad_size);
+        }
+        ripples_texture.insert(water_effect_images.rendered_ripples.clone());
+
+        let mut water_sprites_camera = world.entity_mut(rig.water_sprites_camera);
+        if let Some(mut camera) = water_sprites_camera.get_mut::<Camera>() {
+            camera.target = RenderTarget::Image(water_effect_images.rendered_water_sprites.clone());
+        }
+        water_sprites_camera.insert(water_effect_images.water_sprites_render_layer());
+
+        let mut ripples_camera = world.entity_mut(rig.ripples_camera);
+        if let Some(mut camera) = ripples_camera.get_mut::<Camera>() {
+            camera.target = RenderTarget::Image(water_effect_images.rendered_ripples.clone());
+        }
+        ripples_camera.insert(water_effect_images);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+    use bevy::render::render_resource::Extent3d;
+    use bevy::transform::TransformSystem;
+
+    use crate::components::{
+        follow_main_camera, resize_water_effect_images, MainCamera, RipplesCamera, RipplesTexture,
+        WaterSpritesCamera,
+    };
+    use crate::settings::WaterEffectSettings;
+
+    fn world() -> World {
+        let mut app = App::new();
+        app.add_plugin(bevy::core::CorePlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Image>()
+            .add_asset::<RipplesStyle>()
+            .insert_resource(WaterEffectSettings::default());
+        std::mem::take(&mut app.world)
+    }
+
+    fn spawn(world: &mut World) -> WaterEffectRig {
+        let style = world
+            .resource_mut::<Assets<RipplesStyle>>()
+            .add(RipplesStyle::default());
+        let mut queue = CommandQueue::default();
+        let rig = Commands::new(&mut queue, world).spawn_water_effect(style);
+        queue.apply(world);
+        rig
+    }
+
+    #[test]
+    fn spawns_the_rig_under_one_root() {
+        let mut world = world();
+        let rig = spawn(&mut world);
+
+        assert_eq!(world.get::<WaterEffectRig>(rig.root), Some(&rig));
+        assert!(world.get::<MainCamera>(rig.main_camera).is_some());
+        assert!(world.get::<WaterSpritesCamera>(rig.water_sprites_camera).is_some());
+        assert!(world.get::<RipplesCamera>(rig.ripples_camera).is_some());
+        assert!(world.get::<RipplesTexture>(rig.ripples_texture).is_some());
+
+        let children = world.get::<Children>(rig.root).unwrap();
+        assert_eq!(
+            &children[..],
+            &[
+                rig.main_camera,
+                rig.water_sprites_camera,
+                rig.ripples_camera,
+                rig.ripples_texture
+            ]
+        );
+    }
+
+    #[test]
+    fn each_rig_renders_into_its_own_images() {
+        let mut world = world();
+        let rig = spawn(&mut world);
+        let other = spawn(&mut world);
+
+        let images = world.get::<WaterEffectImages>(rig.ripples_camera).unwrap();
+        let other_images = world.get::<WaterEffectImages>(other.ripples_camera).unwrap();
+        assert_ne!(images.rendered_water_sprites, other_images.rendered_water_sprites);
+        assert_ne!(images.rendered_ripples, other_images.rendered_ripples);
+
+        let target = |entity| world.get::<Camera>(entity).unwrap().target.clone();
+        assert_eq!(
+            target(rig.water_sprites_camera),
+            RenderTarget::Image(images.rendered_water_sprites.clone())
+        );
+        assert_eq!(
+            target(rig.ripples_camera),
+            RenderTarget::Image(images.rendered_ripples.clone())
+        );
+        assert_eq!(
+            world.get::<Handle<Image>>(rig.ripples_texture),
+            Some(&images.rendered_ripples)
+        );
+    }
+
+    #[test]
+    fn each_rig_is_sized_from_its_own_main_camera() {
+        let mut world = world();
+        world.init_resource::<Windows>();
+        let rigs = [spawn(&mut world), spawn(&mut world)];
+
+        let sizes = [UVec2::new(320, 240), UVec2::new(64, 128)];
+        for (rig, size) in rigs.iter().zip(sizes) {
+            let mut target = Image::default();
+            target.resize(Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            });
+            let target = world.resource_mut::<Assets<Image>>().add(target);
+            world.get_mut::<Camera>(rig.main_camera).unwrap().target = RenderTarget::Image(target);
+        }
+
+        let mut stage = SystemStage::single(resize_water_effect_images);
+        stage.run(&mut world);
+
+        for (rig, size) in rigs.iter().zip(sizes) {
+            let water_effect_images = world.get::<WaterEffectImages>(rig.ripples_camera).unwrap();
+            let images = world.resource::<Assets<Image>>();
+            for handle in [
+                &water_effect_images.rendered_water_sprites,
+                &water_effect_images.rendered_ripples,
+            ] {
+                assert_eq!(images.get(handle).unwrap().size(), size.as_vec2());
+            }
+            let sprite = world.get::<Sprite>(rig.ripples_texture).unwrap();
+            assert_eq!(sprite.custom_size, Some(size.as_vec2()));
+        }
+    }
+
+    #[test]
+    fn despawns_as_a_unit() {
+        let mut world = world();
+        let rig = spawn(&mut world);
+        let other = spawn(&mut world);
+
+        let mut queue = CommandQueue::default();
+        rig.despawn(&mut Commands::new(&mut queue, &world));
+        queue.apply(&mut world);
+
+        for entity in [
+            rig.root,
+            rig.main_camera,
+            rig.water_sprites_camera,
+            rig.ripples_camera,
+            rig.ripples_texture,
+        ] {
+            assert!(world.get_entity(entity).is_none(), "{:?} was left", entity);
+        }
+        assert!(world.get_entity(other.main_camera).is_some());
+    }
+
+    #[test]
+    fn each_rig_follows_its_own_main_camera() {
+        let mut world = world();
+        let rigs = [spawn(&mut world), spawn(&mut world)];
+
+        let views = [(Vec3::new(-300.0, 40.0, 0.0), 0.5), (Vec3::new(250.0, -120.0, 0.0), 3.0)];
+        for (rig, (translation, scale)) in rigs.iter().zip(views) {
+            world.get_mut::<Transform>(rig.main_camera).unwrap().translation += translation;
+            world.get_mut::<OrthographicProjection>(rig.main_camera).unwrap().scale = scale;
+        }
+
+        let mut stage = SystemStage::parallel()
+            .with_system(follow_main_camera.before(TransformSystem::TransformPropagate))
+            .with_system(
+                bevy::transform::transform_propagate_system.label(TransformSystem::TransformPropagate),
+            );
+        stage.run(&mut world);
+
+        for (rig, (_, scale)) in rigs.iter().zip(views) {
+            let main = world.get::<GlobalTransform>(rig.main_camera).unwrap().compute_matrix();
+            for camera in [rig.water_sprites_camera, rig.ripples_camera] {
+                let global = world.get::<GlobalTransform>(camera).unwrap().compute_matrix();
+                assert!(global.abs_diff_eq(main, 1e-4), "{:?} is at {}, not {}", camera, global, main);
+                assert_eq!(world.get::<OrthographicProjection>(camera).unwrap().scale, scale);
+            }
+            let sprite = world.get::<GlobalTransform>(rig.ripples_texture).unwrap();
+            assert!(sprite.translation().truncate().abs_diff_eq(main.w_axis.truncate().truncate(), 1e-4));
+            assert_eq!(sprite.compute_transform().scale.x, scale);
+        }
+    }
+
+    #[test]
+    fn water_cameras_follow_a_parented_main_camera() {
+        let mut world = world();
+        let rig = spawn(&mut world);
+
+        // The rig is moved, and the main camera sits on a dolly inside it.
+        world.get_mut::<Transform>(rig.root).unwrap().translation = Vec3::new(100.0, 50.0, 0.0);
+        let dolly = world
+            .spawn()
+            .insert_bundle(SpatialBundle::from_transform(
+                Transform::from_xyz(-40.0, 20.0, 0.0).with_rotation(Quat::from_rotation_z(0.5)),
+            ))
+            .id();
+        world.entity_mut(rig.root).push_children(&[dolly]);
+        world.entity_mut(dolly).push_children(&[rig.main_camera]);
+        world.get_mut::<Transform>(rig.main_camera).unwrap().translation.x = 10.0;
+
+        let mut stage = SystemStage::parallel()
+            .with_system(follow_main_camera.before(TransformSystem::TransformPropagate))
+            .with_system(
+                bevy::transform::transform_propagate_system.label(TransformSystem::TransformPropagate),
+            );
+        stage.run(&mut world);
+
+        let main = world.get::<GlobalTransform>(rig.main_camera).unwrap().compute_matrix();
+        for camera in [rig.water_sprites_camera, rig.ripples_camera] {
+            let global = world.get::<GlobalTransform>(camera).unwrap().compute_matrix();
+            assert!(global.abs_diff_eq(main, 1e-4), "{:?} is at {}, not {}", camera, global, main);
+        }
+        let sprite = world.get::<GlobalTransform>(rig.ripples_texture).unwrap().translation();
+        assert!(sprite.truncate().abs_diff_eq(main.w_axis.truncate().truncate(), 1e-4));
+    }
+}
@@ -0,0 +1,330 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::*;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::Extract;
+use bevy::utils::HashMap;
+
+use crate::components::RipplesCamera;
+use crate::resources::WaterEffectResources;
+use crate::uniforms::{RippleImpulseUniform, RippleImpulses};
+
+pub use crate::uniforms::MAX_RIPPLE_IMPULSES;
+
+/// A splash on the water, sent as an event by gameplay code.
+///
+/// Rings spread from `position` until they reach `radius` and fade out after
+/// `lifetime` seconds. They are only drawn over water. At most
+/// [`MAX_RIPPLE_IMPULSES`] impulses are drawn at once, the oldest are dropped
+/// first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RippleImpulse {
+    /// Centre of the splash, in world space.
+    pub position: Vec2,
+    /// How far the rings tint the water towards the ripples colour, from 0
+    /// to 1.
+    pub strength: f32,
+    /// Radius in world units the rings spread to over the lifetime.
+    pub radius: f32,
+    /// Seconds until the rings have faded out.
+    pub lifetime: f32,
+}
+
+impl RippleImpulse {
+    pub fn new(position: Vec2, strength: f32, radius: f32, lifetime: f32) -> Self {
+        Self {
+            position,
+            strength,
+            radius,
+            lifetime,
+        }
+    }
+
+    /// The impulse in the view pixels of `camera`, `age` seconds after it was
+    /// sent, or `None` if it is out of the camera's depth range.
+    fn to_view(
+        self,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        age: f32,
+    ) -> Option<RippleImpulseUniform> {
+        if self.lifetime <= 0.0 {
+            return None;
+        }
+
+        let viewport_size = camera.logical_viewport_size()?;
+        let centre = camera.world_to_viewport(camera_transform, self.position.extend(0.0))?;
+        let edge = camera.world_to_viewport(
+            camera_transform,
+            (self.position + Vec2::X * self.radius).extend(0.0),
+        )?;
+        let progress = (age / self.lifetime).clamp(0.0, 1.0);
+
+        Some(RippleImpulseUniform {
+            // The viewport has y up, the ripples shader y down.
+            position: Vec2::new(centre.x, viewport_size.y - centre.y),
+            front: progress * centre.distance(edge),
+            amplitude: self.strength * (1.0 - progress),
+        })
+    }
+}
+
+/// Impulses whose rings are still spreading, oldest first, with the time
+/// they were sent at.
+#[derive(Default)]
+pub(crate) struct ActiveRippleImpulses(Vec<(RippleImpulse, f32)>);
+
+pub(crate) fn collect_ripple_impulses(
+    time: Res<Time>,
+    mut events: EventReader<RippleImpulse>,
+    mut active: ResMut<ActiveRippleImpulses>,
+) {
+    let now = time.seconds_since_startup() as f32;
+
+    active.0.retain(|(impulse, sent)| now - sent < impulse.lifetime);
+    active.0.extend(events.iter().map(|impulse| (*impulse, now)));
+
+    let excess = active.0.len().saturating_sub(MAX_RIPPLE_IMPULSES);
+    active.0.drain(..excess);
+}
+
+#[derive(Component)]
+pub(crate) struct ExtractedRippleImpulses(RippleImpulses);
+
+type RipplesCameraViews<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Camera, &'static GlobalTransform),
+    With<RipplesCamera>,
+>;
+
+pub(crate) fn extract_ripple_impulses(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    time: Extract<Res<Time>>,
+    active: Extract<Res<ActiveRippleImpulses>>,
+    cameras: Extract<RipplesCameraViews>,
+) {
+    let now = time.seconds_since_startup() as f32;
+
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(cameras.iter().map(|(entity, camera, camera_transform)| {
+        let mut impulses = RippleImpulses::default();
+        let in_view = active
+            .0
+            .iter()
+            .filter_map(|(impulse, sent)| impulse.to_view(camera, camera_transform, now - sent));
+        for (slot, impulse) in impulses.impulses.iter_mut().zip(in_view) {
+            *slot = impulse;
+            impulses.count += 1;
+        }
+        (entity, (ExtractedRippleImpulses(impulses),))
+    }));
+    *previous_len = batches.len();
+
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// Bind group 3 of the ripples pass, with the time and the impulses of the
+/// view.
+#[derive(Component)]
+pub(crate) struct ViewRippleImpulses {
+    pub(crate) bind_group: BindGroup,
+}
+
+/// Impulses uniform and bind group of a view, kept from frame to frame.
+pub(crate) struct PreparedRippleImpulses {
+    buffer: UniformBuffer<RippleImpulses>,
+    bind_group: BindGroup,
+}
+
+/// Inserts [`ViewRippleImpulses`] on every view with extracted impulses.
+///
+/// The buffer and bind group of a view are created once, the buffer is only
+/// rewritten when its impulses change.
+pub(crate) fn prepare_ripple_impulses(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    water_effect_res: Res<WaterEffectResources>,
+    mut previous_views: Local<HashMap<Entity, PreparedRippleImpulses>>,
+    views: Query<(Entity, &ExtractedRippleImpulses)>,
+) {
+    let mut prepared_views = HashMap::default();
+
+    for (entity, impulses) in views.iter() {
+        let prepared = match previous_views.remove(&entity) {
+            Some(mut prepared) => {
+                if *prepared.buffer.get() != impulses.0 {
+                    prepared.buffer.set(impulses.0.clone());
+                    prepared.buffer.write_buffer(&device, &queue);
+                }
+                prepared
+            }
+            None => {
+                let mut buffer = UniformBuffer::from(impulses.0.clone());
+                buffer.write_buffer(&device, &queue);
+
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("water_effect_ripples_time_bind_group"),
+                    layout: &water_effect_res.ripples_time_bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: water_effect_res.ripples_time_uniform_buffer.as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: buffer.binding().unwrap(),
+                        },
+                    ],
+                });
+                PreparedRippleImpulses { buffer, bind_group }
+            }
+        };
+
+        commands.entity(entity).insert(ViewRippleImpulses {
+            bind_group: prepared.bind_group.clone(),
+        });
+        prepared_views.insert(entity, prepared);
+    }
+
+    *previous_views = prepared_views;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::render::camera::{camera_system, RenderTarget};
+    use bevy::render::MainWorld;
+    use bevy::window::{WindowCreated, WindowResized};
+
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_impulses_until_they_fade_out() {
+        let mut app = App::new();
+        app.add_plugin(bevy::core::CorePlugin)
+            .init_resource::<Time>()
+            .init_resource::<ActiveRippleImpulses>()
+            .add_event::<RippleImpulse>()
+            .add_system(collect_ripple_impulses);
+
+        let splash = |lifetime| RippleImpulse::new(Vec2::ZERO, 1.0, 10.0, lifetime);
+        app.world.send_event(splash(0.5));
+        for _ in 0..MAX_RIPPLE_IMPULSES {
+            app.world.send_event(splash(2.0));
+        }
+        app.update();
+
+        let active = &app.world.resource::<ActiveRippleImpulses>().0;
+        assert_eq!(active.len(), MAX_RIPPLE_IMPULSES);
+        assert!(active.iter().all(|(impulse, _)| impulse.lifetime == 2.0));
+
+        set_seconds_since_startup(&mut app, 1.0);
+        app.world.send_event(splash(0.5));
+        app.update();
+        assert_eq!(app.world.resource::<ActiveRippleImpulses>().0.len(), MAX_RIPPLE_IMPULSES);
+
+        set_seconds_since_startup(&mut app, 2.0);
+        app.update();
+        assert!(app.world.resource::<ActiveRippleImpulses>().0.is_empty());
+    }
+
+    fn set_seconds_since_startup(app: &mut App, seconds: f32) {
+        let mut time = app.world.resource_mut::<Time>();
+        let instant = time.startup() + Duration::from_secs_f32(seconds);
+        time.update_with_instant(instant);
+    }
+
+    /// A ripples camera at `x`, rendering into a 200x100 image.
+    fn spawn_camera(world: &mut World, x: f32) -> Entity {
+        let mut target = Image::default();
+        target.resize(Extent3d {
+            width: 200,
+            height: 100,
+            depth_or_array_layers: 1,
+        });
+        let target = world.resource_mut::<Assets<Image>>().add(target);
+        world
+            .spawn()
+            .insert(RipplesCamera)
+            .insert(Camera {
+                target: RenderTarget::Image(target),
+                ..default()
+            })
+            .insert(OrthographicProjection::default())
+            .insert(GlobalTransform::from_xyz(x, 0.0, 999.0))
+            .id()
+    }
+
+    fn main_world() -> World {
+        let mut app = App::new();
+        app.add_plugin(bevy::core::CorePlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Image>()
+            .init_resource::<Windows>()
+            .init_resource::<Time>()
+            .init_resource::<ActiveRippleImpulses>()
+            .add_event::<WindowCreated>()
+            .add_event::<WindowResized>();
+        std::mem::take(&mut app.world)
+    }
+
+    /// Computes the projections of the cameras spawned so far.
+    fn update_cameras(world: &mut World) {
+        SystemStage::single(camera_system::<OrthographicProjection>).run(world);
+    }
+
+    #[test]
+    fn converts_impulses_to_view_pixels() {
+        let mut world = main_world();
+        let entity = spawn_camera(&mut world, 10.0);
+        update_cameras(&mut world);
+        let camera = world.get::<Camera>(entity).unwrap();
+        let camera_transform = world.get::<GlobalTransform>(entity).unwrap();
+
+        let splash = RippleImpulse::new(Vec2::new(20.0, 10.0), 0.8, 30.0, 2.0);
+        let uniform = splash.to_view(camera, camera_transform, 0.5).unwrap();
+        // 10 pixels right of the view centre and 10 above it, in a 200x100 view.
+        assert!(uniform.position.abs_diff_eq(Vec2::new(110.0, 40.0), 1e-4));
+        assert!((uniform.front - 7.5).abs() < 1e-4);
+        assert!((uniform.amplitude - 0.6).abs() < 1e-4);
+
+        let spread = splash.to_view(camera, camera_transform, 5.0).unwrap();
+        assert!((spread.front - 30.0).abs() < 1e-4);
+        assert_eq!(spread.amplitude, 0.0);
+
+        let instant = RippleImpulse::new(Vec2::ZERO, 1.0, 30.0, 0.0);
+        assert_eq!(instant.to_view(camera, camera_transform, 0.0), None);
+    }
+
+    #[test]
+    fn packs_the_impulses_of_each_view() {
+        let mut main_world = main_world();
+        let cameras = [spawn_camera(&mut main_world, 0.0), spawn_camera(&mut main_world, 50.0)];
+        update_cameras(&mut main_world);
+        main_world.resource_mut::<ActiveRippleImpulses>().0 = vec![
+            (RippleImpulse::new(Vec2::new(-20.0, 0.0), 1.0, 10.0, 1.0), 0.0),
+            // Never drawn, it doesn't take a slot.
+            (RippleImpulse::new(Vec2::ZERO, 1.0, 10.0, 0.0), 0.0),
+            (RippleImpulse::new(Vec2::new(40.0, -30.0), 0.5, 10.0, 1.0), 0.0),
+        ];
+
+        let mut render_world = World::new();
+        render_world.init_resource::<MainWorld>();
+        std::mem::swap(&mut **render_world.resource_mut::<MainWorld>(), &mut main_world);
+        SystemStage::single(extract_ripple_impulses).run(&mut render_world);
+
+        for (camera, x) in cameras.into_iter().zip([0.0, 50.0]) {
+            let impulses = &render_world.get::<ExtractedRippleImpulses>(camera).unwrap().0;
+            assert_eq!(impulses.count, 2);
+            let positions = impulses.impulses[..2].iter().map(|impulse| impulse.position);
+            for (position, expected) in positions.zip([Vec2::new(80.0, 50.0), Vec2::new(140.0, 80.0)]) {
+                assert!(position.abs_diff_eq(expected - Vec2::new(x, 0.0), 1e-4));
+            }
+            assert_eq!(impulses.impulses[1].amplitude, 0.5);
+            assert!(impulses.impulses[2..].iter().all(|impulse| *impulse == default()));
+        }
+    }
+}
@@ -11,10 +11,16 @@ use bevy::{
     },
 };
 
-use crate::{components::RipplesCamera};
+use crate::components::{RipplesCamera, WaterEffectImages};
+use crate::diagnostics::verbose;
+use crate::error::{ErrorReporter, WaterEffectError};
+use crate::ripple_impulse::ViewRippleImpulses;
 use crate::ripples_style::RipplesStyle;
+use crate::status::PipelineTracker;
+use crate::style_transition::{view_ripples_params, BlendedRipplesParams};
 use crate::{
-    resources::{self, WaterEffectResources},
+    resources::{self, ViewWaterEffectResources},
+    settings::WaterEffectSettings,
     FULLSCREEN_PRIMITIVE_STATE,
 };
 
@@ -25,6 +31,8 @@ pub struct RipplesPipeline {
     params_layout: BindGroupLayout,
     time_layout: BindGroupLayout,
     shader: Handle<Shader>,
+    coast_direction_output: bool,
+    distance_field_output: bool,
 }
 
 impl FromWorld for RipplesPipeline {
@@ -38,8 +46,9 @@ impl FromWorld for RipplesPipeline {
         let params_layout = res.ripples_params_bind_group_layout.clone();
         let time_layout = res.ripples_time_bind_group_layout.clone();
 
+        let settings = world.resource::<WaterEffectSettings>();
         let asset_server = world.resource::<AssetServer>();
-        let shader = asset_server.load("shaders/ripples.wgsl");
+        let shader = asset_server.load(&settings.shaders.ripples);
 
         RipplesPipeline {
             dimensions_layout,
@@ -47,6 +56,8 @@ impl FromWorld for RipplesPipeline {
             params_layout,
             time_layout,
             shader,
+            coast_direction_output: settings.coast_direction_output,
+            distance_field_output: settings.distance_field_output,
         }
     }
 }
@@ -94,6 +105,32 @@ impl SpecializedRenderPipeline for RipplesPipeline {
             },
         };
 
+        let mut shader_defs = vec![];
+        let mut targets = vec![Some(ColorTargetState {
+            format: key.format,
+            blend: Some(blend),
+            write_mask: ColorWrites::ALL,
+        })];
+        // Each output keeps its location, whether the ones before it are
+        // written or not.
+        let output_target = |enabled: bool, format: TextureFormat| {
+            enabled.then_some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })
+        };
+        if self.coast_direction_output {
+            shader_defs.push("COAST_DIRECTION".into());
+        }
+        if self.distance_field_output {
+            shader_defs.push("DISTANCE_FIELD".into());
+            targets.push(output_target(self.coast_direction_output, TextureFormat::Rg16Float));
+            targets.push(output_target(true, TextureFormat::R16Float));
+        } else if self.coast_direction_output {
+            targets.push(output_target(true, TextureFormat::Rg16Float));
+        }
+
         RenderPipelineDescriptor {
             label: Some("jfa_ripples_pipeline".into()),
             layout: Some(vec![
@@ -110,13 +147,9 @@ impl SpecializedRenderPipeline for RipplesPipeline {
             },
             fragment: Some(FragmentState {
                 shader: self.shader.clone(),
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: "fragment".into(),
-                targets: vec![Some(ColorTargetState {
-                    format: key.format,
-                    blend: Some(blend),
-                    write_mask: ColorWrites::ALL,
-                })],
+                targets,
             }),
             primitive: FULLSCREEN_PRIMITIVE_STATE,
             depth_stencil: None,
@@ -129,9 +162,17 @@ impl SpecializedRenderPipeline for RipplesPipeline {
     }
 }
 
+type RipplesCameraView = (
+    &'static ExtractedCamera,
+    &'static Handle<RipplesStyle>,
+    &'static ViewWaterEffectResources,
+    &'static ViewRippleImpulses,
+    &'static WaterEffectImages,
+);
+
 pub struct RipplesNode {
     pipeline_id: CachedRenderPipelineId,
-    camera_query: QueryState<(&'static ExtractedCamera, &'static Handle<RipplesStyle>), With<RipplesCamera>>,
+    camera_query: QueryState<RipplesCameraView, With<RipplesCamera>>,
 }
 
 impl RipplesNode {
@@ -139,23 +180,30 @@ impl RipplesNode {
     pub const IN_JFA: &'static str = "in_jfa";
     pub const OUT_VIEW: &'static str = "out_view";
 
-    pub fn new(world: &mut World, target_format: TextureFormat) -> RipplesNode {
+    pub fn new(world: &mut World, target_format: TextureFormat) -> Result<RipplesNode, WaterEffectError> {
+        let key = RipplesPipelineKey::new(target_format).ok_or_else(|| {
+            WaterEffectError::PipelineCompile {
+                pipeline: "ripples",
+                message: format!("{:?} cannot be used as a render target", target_format),
+            }
+        })?;
+
         let pipeline_id = world.resource_scope(|world, mut cache: Mut<PipelineCache>| {
             let base = world.get_resource::<RipplesPipeline>().unwrap().clone();
             let mut spec = world
                 .get_resource_mut::<SpecializedRenderPipelines<RipplesPipeline>>()
                 .unwrap();
-            let key = RipplesPipelineKey::new(target_format)
-                .expect("invalid format for RipplesNode");
             spec.specialize(&mut cache, &base, key)
         });
-
-        dbg!(&pipeline_id);
+        world.resource_mut::<PipelineTracker>().track("ripples", pipeline_id);
 
         let camera_query = QueryState::new(world);
         // let ripples_query = QueryState::new(world);
 
-        RipplesNode { pipeline_id, camera_query }//, ripples_query }
+        Ok(RipplesNode {
+            pipeline_id,
+            camera_query,
+        })
     }
 }
 
@@ -191,85 +239,112 @@ impl Node for RipplesNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        
+        let _span = bevy::log::info_span!("ripples").entered();
 
         let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
-
-        dbg!(&view_ent);
-
         graph.set_output(Self::OUT_VIEW, view_ent)?;
 
-        let (extracted_camera, styles_handle) = &self.camera_query.get_manual(world, view_ent).unwrap();
-
-        bevy::log::info!("found extracted camera");
-        // dbg!(&extracted_camera);
+        let error_reporter = world.resource::<ErrorReporter>();
+        let (extracted_camera, styles_handle, view_res, impulses, water_effect_images) = match self.camera_query.get_manual(world, view_ent) {
+            Ok(camera) => camera,
+            Err(_) => {
+                error_reporter.report(WaterEffectError::MissingCamera(view_ent));
+                return Ok(());
+            }
+        };
 
         let windows = world.resource::<ExtractedWindows>();
         let images = world.resource::<RenderAssets<Image>>();
         let target_view = match extracted_camera.target.get_texture_view(windows, images) {
             Some(v) => v,
-            None => return Ok(()),
+            None => {
+                verbose!("ripples target of {:?} is not available yet", view_ent);
+                return Ok(());
+            }
         };
 
-        bevy::log::info!("found view");
-        // dbg!(&target_view);
+        // Only there with `WaterEffectSettings::coast_direction_output` and
+        // `WaterEffectSettings::distance_field_output`.
+        let coast_direction = water_effect_images.coast_direction.as_ref();
+        let coast_direction_view = match coast_direction.map(|handle| images.get(handle)) {
+            Some(Some(image)) => Some(&image.texture_view),
+            Some(None) => {
+                verbose!("coast direction image of {:?} is not available yet", view_ent);
+                return Ok(());
+            }
+            None => None,
+        };
+        let distance_field = water_effect_images.distance_field.as_ref();
+        let distance_field_view = match distance_field.map(|handle| images.get(handle)) {
+            Some(Some(image)) => Some(&image.texture_view),
+            Some(None) => {
+                verbose!("distance field image of {:?} is not available yet", view_ent);
+                return Ok(());
+            }
+            None => None,
+        };
 
         let styles = world.resource::<RenderAssets<RipplesStyle>>();
-        let style = styles.get(&styles_handle).unwrap();
-
-        bevy::log::info!("found style");
-        // dbg!(&style.params);
-
-        let res = world.get_resource::<WaterEffectResources>().unwrap();
+        let style = match view_ripples_params(styles, world.resource::<BlendedRipplesParams>(), view_ent, styles_handle) {
+            Some(style) => style,
+            None => {
+                error_reporter.report_missing_style(world.resource::<AssetServer>(), styles_handle);
+                return Ok(());
+            }
+        };
 
         let pipelines = world.get_resource::<PipelineCache>().unwrap();
-        let pipeline_state = pipelines.get_render_pipeline_state(self.pipeline_id);
-        // dbg!(pipeline_state);
-
-        match pipeline_state {
-            CachedPipelineState::Err(_) | CachedPipelineState::Queued => return Ok(()),
-            CachedPipelineState::Ok(_) => (),
-        }
-
-        bevy::log::info!("pipeline state is Ok");
-
         let pipeline = match pipelines.get_render_pipeline(self.pipeline_id) {
-            Some(p) => {
-                bevy::log::info!("found pipeline");
-                p
-            },
-            None => {
-                bevy::log::warn!("NOT found pipeline");
-                return Ok(())
-            },
+            Some(p) => p,
+            // Still queued.
+            None => return Ok(()),
         };
 
+        let mut color_attachments = vec![Some(RenderPassColorAttachment {
+            view: target_view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Load,
+                store: true,
+            },
+        })];
+        // Same locations as the targets of the pipeline.
+        if distance_field_view.is_some() {
+            color_attachments.push(output_attachment(coast_direction_view));
+            color_attachments.push(output_attachment(distance_field_view));
+        } else if coast_direction_view.is_some() {
+            color_attachments.push(output_attachment(coast_direction_view));
+        }
+
         let render_pass = render_context
             .command_encoder
             .begin_render_pass(&RenderPassDescriptor {
                 label: Some("water_effect_ripples"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: target_view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Load,
-                        store: true,
-                    },
-                })],
+                color_attachments: &color_attachments,
                 // TODO(from Bevy JFA...): support outlines being occluded by world geometry
                 depth_stencil_attachment: None,
             });
 
-        // dbg!(&render_pass);
-
         let mut tracked_pass = TrackedRenderPass::new(render_pass);
         tracked_pass.set_render_pipeline(pipeline);
-        tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-        tracked_pass.set_bind_group(1, &res.ripples_src_bind_group, &[]);
+        tracked_pass.set_bind_group(0, &view_res.dimensions_bind_group, &[]);
+        tracked_pass.set_bind_group(1, &view_res.ripples_src_bind_group, &[]);
         tracked_pass.set_bind_group(2, &style.bind_group, &[]);
-        tracked_pass.set_bind_group(3, &res.ripples_time_bind_group, &[]);
+        tracked_pass.set_bind_group(3, &impulses.bind_group, &[]);
         tracked_pass.draw(0..4, 0..1);
 
         Ok(())
     }
 }
+
+/// Attachment for an optional output of the ripples pass.
+fn output_attachment(view: Option<&TextureView>) -> Option<RenderPassColorAttachment<'_>> {
+    view.map(|view| RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: Operations {
+            load: LoadOp::Clear(Color::NONE.into()),
+            store: true,
+        },
+    })
+}
@@ -1,5 +1,8 @@
+use std::num::NonZeroU32;
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
 use bevy::prelude::*;
-use bevy::reflect::TypeUuid;
+use bevy::reflect::{FromReflect, TypeUuid};
 use bevy::render::render_resource::*;
 use bevy::{
     ecs::{system::SystemParamItem},
@@ -9,32 +12,132 @@ use bevy::{
     },
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::resources;
 
-#[derive(Clone, Debug, PartialEq, TypeUuid)]
+pub use crate::uniforms::RipplesParams;
+
+/// Number of texels the water gradient is baked into.
+const GRADIENT_WIDTH: usize = 256;
+
+/// Colour of the water at a distance from the coast, see
+/// [`RipplesStyle::gradient`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Reflect, FromReflect)]
+pub struct GradientStop {
+    /// Distance from the coast, normalised so that the coast is at 0 and
+    /// `distance_from_coast` at 1.
+    pub position: f32,
+    pub color: Color,
+}
+
+/// Appearance of the ripples drawn around the coastline.
+///
+/// Distances are measured in view pixels from the nearest coast fragment found
+/// by the jump flood, and times in seconds since startup.
+///
+/// Besides being added in code, styles load from `*.ripples.ron` files, see
+/// [`RipplesStyleLoader`]. Missing fields take their default value.
+#[derive(Clone, Debug, PartialEq, TypeUuid, Serialize, Deserialize, Reflect, FromReflect)]
 #[uuid = "6805d65e-f637-4a49-869a-889c0abe8140"]
+#[serde(default)]
+#[reflect(Default, PartialEq)]
 pub struct RipplesStyle {
+    /// Colour of open water, and of the troughs between ripple bands.
     pub water_color: Color,
+    /// Colour of the water from the coast outwards, used instead of
+    /// `water_color` when not empty.
+    ///
+    /// The stops can be in any order. Colours are blended in linear space
+    /// between stops, and held before the first and after the last one.
+    pub gradient: Vec<GradientStop>,
+    /// Colour of the crest of each ripple band, blended over `water_color`.
     pub ripples_color: Color,
+    /// Distance in pixels at which the ripples have faded out completely.
     pub distance_from_coast: f32,
-    pub frequency: f32, // https://itscai.us/blog/post/jfa/
+    /// Number of bands per pixel of distance, i.e. the reciprocal of the
+    /// wavelength in pixels. See <https://itscai.us/blog/post/jfa/>.
+    pub frequency: f32,
+    /// Speed in pixels per second at which the bands move towards the coast,
+    /// without wind. Negative speeds move them away from the coast.
     pub speed: f32,
+    /// Direction the wind blows towards, relative to the view with y up. Its
+    /// length, up to 1, is the strength of the wind.
+    ///
+    /// The wind does not push the bands. It scales their spacing, down to 2/3
+    /// on coasts it blows straight onto and up to twice on sheltered ones,
+    /// and the speed of the bands along with it, so that they stay in step.
+    /// Bands on sheltered coasts are also dimmed, fading out completely at
+    /// full strength.
+    pub wind_direction: Vec2,
+    /// Colour of the foam along the coastline, blended over the ripples.
+    pub foam_color: Color,
+    /// Average width of the foam in pixels, 0 for no foam.
+    ///
+    /// Noise moves the edge of the foam between half and one and a half
+    /// times this width.
+    pub foam_width: f32,
+    /// Size in pixels of the noise breaking up the edge of the foam.
+    ///
+    /// The noise is laid out over the view, not the world.
+    pub foam_noise_scale: f32,
+    /// Speed in noise cells per second at which the edge of the foam shifts.
+    pub foam_noise_speed: f32,
 }
 
 impl Default for RipplesStyle {
     fn default() -> Self {
         Self {
             water_color: Color::BLUE,
+            gradient: Vec::new(),
             ripples_color: Color::BLACK,
             distance_from_coast: 100.,
-            frequency: 0.5,
-            speed: 1.,
+            frequency: 1. / 16.,
+            speed: 8.,
+            wind_direction: Vec2::ZERO,
+            foam_color: Color::WHITE,
+            foam_width: 0.,
+            foam_noise_scale: 8.,
+            foam_noise_speed: 0.5,
         }
     }
 }
 
+/// Loads [`RipplesStyle`]s from `*.ripples.ron` files.
+///
+/// Colours are written like `Rgba(red: 0.1, green: 0.3, blue: 0.6, alpha: 1.0)`,
+/// in sRGB. With the asset server watching for changes, edits to a file are
+/// picked up while the game runs.
+#[derive(Default)]
+pub struct RipplesStyleLoader;
+
+impl AssetLoader for RipplesStyleLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let style: RipplesStyle = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(style));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ripples.ron"]
+    }
+}
+
+/// A [`RipplesStyle`] extracted to the render world.
+pub struct ExtractedRipplesStyle {
+    params: RipplesParams,
+    water_color: Color,
+    gradient: Vec<GradientStop>,
+}
+
 impl RenderAsset for RipplesStyle {
-    type ExtractedAsset = RipplesParams;
+    type ExtractedAsset = ExtractedRipplesStyle;
     type PreparedAsset = GpuRipplesParams;
     type Param = (
         Res<'static, RenderDevice>,
@@ -43,71 +146,345 @@ impl RenderAsset for RipplesStyle {
     );
 
     fn extract_asset(&self) -> Self::ExtractedAsset {
-        RipplesParams::new(
-            self.water_color,
-            self.ripples_color,
-            self.distance_from_coast,
-            self.frequency,
-            self.speed,
-        )
+        ExtractedRipplesStyle {
+            params: RipplesParams::new(self),
+            water_color: self.water_color,
+            gradient: self.gradient.clone(),
+        }
     }
 
     fn prepare_asset(
         extracted_asset: Self::ExtractedAsset,
         (device, queue, water_effect_res): &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
-        let mut buffer = UniformBuffer::from(extracted_asset.clone());
+        let gradient = bake_gradient(&extracted_asset.gradient, extracted_asset.water_color);
+
+        Ok(GpuRipplesParams::new(
+            extracted_asset.params,
+            gradient,
+            device,
+            queue,
+            water_effect_res,
+        ))
+    }
+}
+
+impl RipplesParams {
+    pub fn new(style: &RipplesStyle) -> RipplesParams {
+        RipplesParams {
+            water_color: style.water_color.as_rgba_f32().into(),
+            ripples_color: style.ripples_color.as_rgba_f32().into(),
+            distance_from_coast: style.distance_from_coast,
+            frequency: style.frequency,
+            speed: style.speed,
+            foam_color: style.foam_color.as_rgba_f32().into(),
+            foam_width: style.foam_width,
+            foam_noise_scale: style.foam_noise_scale,
+            foam_noise_speed: style.foam_noise_speed,
+            wind_direction: style.wind_direction,
+        }
+    }
+
+    /// Interpolates from `self` at `t = 0` to `other` at `t = 1`, with the
+    /// colours blended in linear space.
+    pub fn lerp(&self, other: &RipplesParams, t: f32) -> RipplesParams {
+        let lerp_color = |from: Vec4, to: Vec4| -> Vec4 {
+            let from = Vec4::from(Color::from(from).as_linear_rgba_f32());
+            let to = Vec4::from(Color::from(to).as_linear_rgba_f32());
+            let linear = from.lerp(to, t);
+            Color::rgba_linear(linear.x, linear.y, linear.z, linear.w)
+                .as_rgba_f32()
+                .into()
+        };
+        let lerp = |from: f32, to: f32| from + (to - from) * t;
+
+        RipplesParams {
+            water_color: lerp_color(self.water_color, other.water_color),
+            ripples_color: lerp_color(self.ripples_color, other.ripples_color),
+            distance_from_coast: lerp(self.distance_from_coast, other.distance_from_coast),
+            frequency: lerp(self.frequency, other.frequency),
+            speed: lerp(self.speed, other.speed),
+            foam_color: lerp_color(self.foam_color, other.foam_color),
+            foam_width: lerp(self.foam_width, other.foam_width),
+            foam_noise_scale: lerp(self.foam_noise_scale, other.foam_noise_scale),
+            foam_noise_speed: lerp(self.foam_noise_speed, other.foam_noise_speed),
+            wind_direction: self.wind_direction.lerp(other.wind_direction, t),
+        }
+    }
+}
+
+/// Samples the gradient evenly from 0 to 1, as linear colours. The shader
+/// maps 0 and 1 to the centres of the first and last texels.
+///
+/// An empty gradient is `water_color` everywhere.
+fn bake_gradient(stops: &[GradientStop], water_color: Color) -> Vec<Vec4> {
+    let mut stops: Vec<(f32, Vec4)> = stops
+        .iter()
+        .map(|stop| (stop.position, Vec4::from(stop.color.as_linear_rgba_f32())))
+        .collect();
+    if stops.is_empty() {
+        stops.push((0.0, Vec4::from(water_color.as_linear_rgba_f32())));
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    (0..GRADIENT_WIDTH)
+        .map(|texel| {
+            let position = texel as f32 / (GRADIENT_WIDTH - 1) as f32;
+            let next = stops.partition_point(|stop| stop.0 <= position);
+            if next == 0 {
+                return stops[0].1;
+            }
+            if next == stops.len() {
+                return stops[next - 1].1;
+            }
+
+            let (from, to) = (stops[next - 1], stops[next]);
+            from.1.lerp(to.1, (position - from.0) / (to.0 - from.0))
+        })
+        .collect()
+}
+
+pub struct GpuRipplesParams {
+    pub(crate) params: RipplesParams,
+    /// Linear colours of the baked gradient, kept to blend it.
+    pub(crate) gradient: Vec<Vec4>,
+    buffer: UniformBuffer<RipplesParams>,
+    gradient_texture: Texture,
+    pub(crate) bind_group: BindGroup,
+}
+
+impl GpuRipplesParams {
+    pub(crate) fn new(
+        params: RipplesParams,
+        gradient: Vec<Vec4>,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        water_effect_res: &resources::WaterEffectResources,
+    ) -> GpuRipplesParams {
+        let mut buffer = UniformBuffer::from(params.clone());
         buffer.write_buffer(device, queue);
 
+        let gradient_texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: Some("water_effect_ripples_gradient"),
+                size: gradient_size(&gradient),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D1,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            },
+            &gradient_texels(&gradient),
+        );
+        let gradient_view = gradient_texture.create_view(&TextureViewDescriptor::default());
+
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &water_effect_res.ripples_params_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: buffer.buffer().unwrap().as_entire_binding(),
-            }],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.buffer().unwrap().as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&gradient_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&water_effect_res.ripples_gradient_sampler),
+                },
+            ],
         });
 
-        Ok(GpuRipplesParams {
-            params: extracted_asset,
-            _buffer: buffer,
+        GpuRipplesParams {
+            params,
+            gradient,
+            buffer,
+            gradient_texture,
             bind_group,
-        })
+        }
+    }
+
+    /// Rewrites the params and the gradient in place, keeping the buffer,
+    /// texture and bind group. `gradient` has as many colours as the one the
+    /// params were created with.
+    pub(crate) fn update(
+        &mut self,
+        params: RipplesParams,
+        gradient: impl IntoIterator<Item = Vec4>,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+    ) {
+        self.buffer.set(params.clone());
+        self.buffer.write_buffer(device, queue);
+        self.params = params;
+
+        for (texel, color) in self.gradient.iter_mut().zip(gradient) {
+            *texel = color;
+        }
+        let size = gradient_size(&self.gradient);
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &self.gradient_texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &gradient_texels(&self.gradient),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
     }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, ShaderType)]
-pub struct RipplesParams {
-    pub(crate) water_color: Vec4,
-    pub(crate) ripples_color: Vec4,
-    pub(crate) distance_from_coast: f32,
-    pub(crate) frequency: f32,
-    pub(crate) speed: f32,
+fn gradient_size(gradient: &[Vec4]) -> Extent3d {
+    Extent3d {
+        width: gradient.len() as u32,
+        height: 1,
+        depth_or_array_layers: 1,
+    }
 }
 
-impl RipplesParams {
-    pub fn new(
-        water_color: Color,
-        ripples_color: Color,
-        distance_from_coast: f32,
-        frequency: f32,
-        speed: f32,
-    ) -> RipplesParams {
-        let water_color: Vec4 = water_color.as_rgba_f32().into();
-        let ripples_color: Vec4 = ripples_color.as_rgba_f32().into();
+/// Stored like the colours of the params uniform, which the shader outputs as
+/// they are.
+fn gradient_texels(gradient: &[Vec4]) -> Vec<u8> {
+    gradient
+        .iter()
+        .flat_map(|linear| {
+            Color::rgba_linear(linear.x, linear.y, linear.z, linear.w)
+                .as_rgba_f32()
+                .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+        })
+        .collect()
+}
 
-        RipplesParams {
-            water_color,
-            ripples_color,
-            distance_from_coast,
-            frequency,
-            speed,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_ron() {
+        let style = RipplesStyle {
+            water_color: Color::rgba(0.1, 0.2, 0.3, 0.4),
+            gradient: vec![GradientStop {
+                position: 0.5,
+                color: Color::hsla(200.0, 0.5, 0.5, 1.0),
+            }],
+            ripples_color: Color::rgba_linear(0.5, 0.6, 0.7, 0.8),
+            distance_from_coast: 42.0,
+            frequency: 0.25,
+            speed: 3.0,
+            wind_direction: Vec2::new(0.6, -0.8),
+            foam_color: Color::rgba(0.9, 0.95, 1.0, 0.8),
+            foam_width: 4.0,
+            foam_noise_scale: 6.0,
+            foam_noise_speed: 0.25,
+        };
+
+        let text = ron::to_string(&style).unwrap();
+        assert_eq!(ron::from_str::<RipplesStyle>(&text).unwrap(), style);
     }
-}
 
-pub struct GpuRipplesParams {
-    pub(crate) params: RipplesParams,
-    pub(crate) _buffer: UniformBuffer<RipplesParams>,
-    pub(crate) bind_group: BindGroup,
+    #[test]
+    fn missing_fields_take_their_default() {
+        let style: RipplesStyle = ron::from_str("(speed: 2.0)").unwrap();
+
+        assert_eq!(
+            style,
+            RipplesStyle {
+                speed: 2.0,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn lerps_colours_in_linear_space() {
+        let black = RipplesParams::new(&RipplesStyle {
+            water_color: Color::BLACK,
+            ripples_color: Color::BLACK,
+            distance_from_coast: 0.0,
+            frequency: 0.0,
+            speed: 0.0,
+            ..Default::default()
+        });
+        let white = RipplesParams::new(&RipplesStyle {
+            water_color: Color::WHITE,
+            ripples_color: Color::WHITE,
+            distance_from_coast: 100.0,
+            frequency: 1.0,
+            speed: 10.0,
+            ..Default::default()
+        });
+
+        let halfway = black.lerp(&white, 0.5);
+
+        // Half of linear white is about 0.735 in sRGB, not 0.5.
+        let expected = Color::rgba_linear(0.5, 0.5, 0.5, 1.0).as_rgba_f32();
+        assert!(halfway
+            .water_color
+            .abs_diff_eq(Vec4::from(expected), 1e-5));
+        assert!(halfway.water_color.x > 0.7);
+        assert_eq!(halfway.distance_from_coast, 50.0);
+        assert_eq!(halfway.frequency, 0.5);
+        assert_eq!(halfway.speed, 5.0);
+
+        let start = black.lerp(&white, 0.0);
+        assert!(start.water_color.abs_diff_eq(black.water_color, 1e-5));
+        assert!(start.foam_color.abs_diff_eq(black.foam_color, 1e-5));
+        assert_eq!(start.distance_from_coast, black.distance_from_coast);
+        assert!(black
+            .lerp(&white, 1.0)
+            .ripples_color
+            .abs_diff_eq(white.ripples_color, 1e-5));
+    }
+
+    #[test]
+    fn bakes_the_gradient_between_its_stops() {
+        let stops = [
+            GradientStop {
+                position: 1.0,
+                color: Color::rgba_linear(0.0, 0.0, 1.0, 1.0),
+            },
+            GradientStop {
+                position: 0.5,
+                color: Color::rgba_linear(1.0, 0.0, 0.0, 1.0),
+            },
+        ];
+
+        let gradient = bake_gradient(&stops, Color::WHITE);
+
+        assert_eq!(gradient.len(), GRADIENT_WIDTH);
+        // Held before the first stop.
+        assert_eq!(gradient[0], Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(gradient[GRADIENT_WIDTH / 2 - 1], Vec4::new(1.0, 0.0, 0.0, 1.0));
+        // Blended in linear space after it.
+        let three_quarters = gradient[GRADIENT_WIDTH * 3 / 4];
+        assert!((three_quarters.x + three_quarters.z - 1.0).abs() < 1e-5);
+        assert!((three_quarters.x - 0.5).abs() < 0.01);
+        assert!(gradient[GRADIENT_WIDTH - 1].z > 0.99);
+    }
+
+    #[test]
+    fn empty_gradient_is_the_water_colour() {
+        let gradient = bake_gradient(&[], Color::rgb(0.2, 0.4, 0.6));
+        let water = Vec4::from(Color::rgb(0.2, 0.4, 0.6).as_linear_rgba_f32());
+
+        assert!(gradient.iter().all(|&texel| texel == water));
+    }
+
+    #[test]
+    fn parses_the_bundled_style() {
+        let text = include_str!("../../assets/styles/lagoon.ripples.ron");
+        let style: RipplesStyle = ron::from_str(text).unwrap();
+
+        assert_eq!(style.distance_from_coast, 60.0);
+        assert_eq!(style.gradient.len(), 3);
+    }
 }
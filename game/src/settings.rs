@@ -0,0 +1,146 @@
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use bevy::render::texture::BevyDefault;
+
+/// Configuration of the water effect, handed to
+/// [`WaterEffectPlugin::new`](crate::WaterEffectPlugin::new).
+///
+/// The plugin inserts a copy of it as a resource in both the main and the
+/// render world, so every pipeline and render resource is built from the same
+/// values.
+#[derive(Clone, Debug)]
+pub struct WaterEffectSettings {
+    /// Whether the sprites seen by the water sprites camera are the water or
+    /// the land.
+    pub mask_mode: MaskMode,
+    /// Format of the jump flood textures. Must have at least two signed
+    /// channels, as they store framebuffer coordinates or (-1, -1).
+    ///
+    /// Overridden by [`JfaBackend::Compute`], which needs a storage texture
+    /// format and always uses `Rg32Float`. The plugin warns when this is
+    /// changed from the default to another format along with it.
+    pub jfa_texture_format: TextureFormat,
+    /// How the jump flood passes run on the GPU.
+    pub jfa_backend: JfaBackend,
+    /// Resolution of the jump flood textures relative to the view, in
+    /// `0.1..=1.0`; values outside that range are clamped to it. Lower values,
+    /// e.g. 0.25, make the jump flood cheaper at the cost of blockier ripples
+    /// near the coast.
+    pub jfa_resolution_scale: f32,
+    /// Render layer seen by the water sprites camera.
+    pub water_sprites_render_layer: u8,
+    /// Format of the texture the ripples pass renders into.
+    pub ripples_texture_format: TextureFormat,
+    /// Whether the ripples pass also writes the direction to the nearest
+    /// coast, into [`WaterEffectImages::coast_direction`](crate::WaterEffectImages::coast_direction).
+    pub coast_direction_output: bool,
+    /// Whether a second jump flood runs over the inverted mask, so that the
+    /// ripples pass can write a signed distance to the coast into
+    /// [`WaterEffectImages::distance_field`](crate::WaterEffectImages::distance_field).
+    pub distance_field_output: bool,
+    /// Asset paths of the shaders used by the render graph.
+    pub shaders: WaterEffectShaders,
+}
+
+impl WaterEffectSettings {
+    /// Smallest [`jfa_resolution_scale`](Self::jfa_resolution_scale). Below
+    /// it, the jump flood is too coarse to place the coast.
+    pub const MIN_JFA_RESOLUTION_SCALE: f32 = 0.1;
+
+    /// Format of the jump flood textures for the selected backend.
+    pub(crate) fn jfa_format(&self) -> TextureFormat {
+        match self.jfa_backend {
+            JfaBackend::RenderPass => self.jfa_texture_format,
+            JfaBackend::Compute => TextureFormat::Rg32Float,
+        }
+    }
+
+    /// Whether [`jfa_texture_format`](Self::jfa_texture_format) was changed,
+    /// but is overridden by the backend.
+    pub(crate) fn jfa_texture_format_overridden(&self) -> bool {
+        self.jfa_texture_format != self.jfa_format()
+            && self.jfa_texture_format != Self::default().jfa_texture_format
+    }
+
+    /// Usages of the jump flood textures for the selected backend.
+    pub(crate) fn jfa_usages(&self) -> TextureUsages {
+        // COPY_SRC for headless readback.
+        let usages = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC;
+        match self.jfa_backend {
+            JfaBackend::RenderPass => usages | TextureUsages::RENDER_ATTACHMENT,
+            JfaBackend::Compute => usages | TextureUsages::STORAGE_BINDING,
+        }
+    }
+}
+
+impl Default for WaterEffectSettings {
+    fn default() -> Self {
+        Self {
+            mask_mode: MaskMode::default(),
+            jfa_texture_format: TextureFormat::Rg16Snorm,
+            jfa_backend: JfaBackend::default(),
+            jfa_resolution_scale: 1.0,
+            water_sprites_render_layer: 1,
+            ripples_texture_format: TextureFormat::bevy_default(),
+            coast_direction_output: false,
+            distance_field_output: false,
+            shaders: WaterEffectShaders::default(),
+        }
+    }
+}
+
+/// Implementation of the JFA init and jump flood passes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JfaBackend {
+    /// One fullscreen render pass per jump distance.
+    #[default]
+    RenderPass,
+    /// One compute dispatch per jump distance, writing storage textures.
+    Compute,
+}
+
+/// Meaning of the sprites rendered by the water sprites camera.
+///
+/// The water mask is the land coverage of each fragment in `0.0..=1.0`,
+/// derived from the alpha of the rendered sprites. Fully covered fragments
+/// seed the jump flood, and ripples are drawn over the rest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskMode {
+    /// The sprites are the water: land coverage is one minus sprite alpha.
+    #[default]
+    WaterFromSprites,
+    /// The sprites are the coastline: land coverage is the sprite alpha.
+    LandFromSprites,
+}
+
+impl MaskMode {
+    /// Shader defs selecting this mode in `mask.wgsl`.
+    pub(crate) fn shader_defs(self) -> Vec<String> {
+        match self {
+            MaskMode::WaterFromSprites => vec!["WATER_FROM_SPRITES".into()],
+            MaskMode::LandFromSprites => vec![],
+        }
+    }
+}
+
+/// Asset paths of the shaders loaded by the water effect pipelines.
+#[derive(Clone, Debug)]
+pub struct WaterEffectShaders {
+    pub mask: String,
+    pub jfa_init: String,
+    pub jfa: String,
+    /// Used instead of `jfa_init` and `jfa` by [`JfaBackend::Compute`].
+    pub jfa_compute: String,
+    pub ripples: String,
+}
+
+impl Default for WaterEffectShaders {
+    fn default() -> Self {
+        Self {
+            mask: "shaders/mask.wgsl".into(),
+            jfa_init: "shaders/jfa_init.wgsl".into(),
+            jfa: "shaders/jfa.wgsl".into(),
+            jfa_compute: "shaders/jfa_compute.wgsl".into(),
+            ripples: "shaders/ripples.wgsl".into(),
+        }
+    }
+}
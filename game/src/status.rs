@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{CachedComputePipelineId, CachedRenderPipelineId, PipelineCache},
+};
+
+use crate::{
+    diagnostics::PipelineReadiness,
+    error::{ErrorReporter, WaterEffectError},
+};
+
+/// Whether the water effect pipelines are ready to render.
+///
+/// Until every pipeline has compiled, the water effect renders nothing.
+/// A loading screen can wait for [`WaterEffectStatus::Ready`], or for the
+/// [`WaterEffectReady`] event.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum WaterEffectStatus {
+    /// Shaders are loading or pipelines are compiling.
+    #[default]
+    Compiling,
+    /// Every pipeline compiled, the water effect is rendering.
+    Ready,
+    /// A pipeline failed to compile, or the render graph could not be built.
+    Failed(WaterEffectError),
+}
+
+/// Sent when [`WaterEffectStatus`] becomes [`WaterEffectStatus::Ready`].
+///
+/// This happens again if the status goes back to compiling, e.g. when a
+/// shader is hot reloaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WaterEffectReady;
+
+/// Carries the latest status from the render world to the main world.
+///
+/// The same instance is inserted in both worlds.
+#[derive(Clone, Default)]
+pub(crate) struct StatusMirror(Arc<Mutex<WaterEffectStatus>>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum TrackedPipelineId {
+    Render(CachedRenderPipelineId),
+    Compute(CachedComputePipelineId),
+}
+
+impl From<CachedRenderPipelineId> for TrackedPipelineId {
+    fn from(id: CachedRenderPipelineId) -> Self {
+        TrackedPipelineId::Render(id)
+    }
+}
+
+impl From<CachedComputePipelineId> for TrackedPipelineId {
+    fn from(id: CachedComputePipelineId) -> Self {
+        TrackedPipelineId::Compute(id)
+    }
+}
+
+struct TrackedPipeline {
+    name: &'static str,
+    readiness: Option<PipelineReadiness>,
+}
+
+/// Every pipeline queued by the water effect, in the render world.
+#[derive(Default)]
+pub(crate) struct PipelineTracker {
+    // In the order they started being tracked, so that the first failure
+    // reported is the same from one run to the next.
+    pipelines: Vec<(TrackedPipelineId, TrackedPipeline)>,
+    failure: Option<WaterEffectError>,
+}
+
+impl PipelineTracker {
+    /// Starts tracking `id`, unless it already is.
+    pub fn track(&mut self, name: &'static str, id: impl Into<TrackedPipelineId>) {
+        let id = id.into();
+        if self.pipelines.iter().all(|(tracked, _)| *tracked != id) {
+            self.pipelines.push((id, TrackedPipeline { name, readiness: None }));
+        }
+    }
+
+    /// Marks the water effect as failed for good, for errors that do not
+    /// come from a pipeline.
+    pub fn fail(&mut self, error: WaterEffectError) {
+        self.failure.get_or_insert(error);
+    }
+}
+
+/// Aggregates the state of the tracked pipelines into a [`WaterEffectStatus`].
+///
+/// Runs after the pipeline cache processed its queue for the frame.
+pub(crate) fn track_pipelines(
+    mut tracker: ResMut<PipelineTracker>,
+    pipeline_cache: Res<PipelineCache>,
+    error_reporter: Res<ErrorReporter>,
+    mirror: Res<StatusMirror>,
+) {
+    let tracker = &mut *tracker;
+    let mut status = match &tracker.failure {
+        Some(err) => WaterEffectStatus::Failed(err.clone()),
+        None => WaterEffectStatus::Ready,
+    };
+
+    for (id, pipeline) in tracker.pipelines.iter_mut() {
+        let state = match *id {
+            TrackedPipelineId::Render(id) => pipeline_cache.get_render_pipeline_state(id),
+            TrackedPipelineId::Compute(id) => pipeline_cache.get_compute_pipeline_state(id),
+        };
+        let readiness = PipelineReadiness::from(state);
+        if pipeline.readiness != Some(readiness) {
+            debug!("{} pipeline: {:?}", pipeline.name, readiness);
+            pipeline.readiness = Some(readiness);
+        }
+
+        match readiness {
+            PipelineReadiness::Ready => {}
+            PipelineReadiness::Compiling => {
+                if status == WaterEffectStatus::Ready {
+                    status = WaterEffectStatus::Compiling;
+                }
+            }
+            PipelineReadiness::Failed => {
+                if let Some(err) = WaterEffectError::from_pipeline_state(pipeline.name, state) {
+                    error_reporter.report(err.clone());
+                    if !matches!(status, WaterEffectStatus::Failed(_)) {
+                        status = WaterEffectStatus::Failed(err);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut mirrored = mirror.0.lock().unwrap();
+    if *mirrored != status {
+        debug!("water effect status: {:?}", status);
+        *mirrored = status;
+    }
+}
+
+pub(crate) fn sync_water_effect_status(
+    mirror: Res<StatusMirror>,
+    mut status: ResMut<WaterEffectStatus>,
+    mut ready_events: EventWriter<WaterEffectReady>,
+) {
+    let latest = mirror.0.lock().unwrap();
+    if *status == *latest {
+        return;
+    }
+
+    if *latest == WaterEffectStatus::Ready {
+        ready_events.send(WaterEffectReady);
+    }
+    *status = latest.clone();
+}
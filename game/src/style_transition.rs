@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use bevy::reflect::FromReflect;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::Extract;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::components::RipplesCamera;
+use crate::resources::WaterEffectResources;
+use crate::ripples_style::{GpuRipplesParams, RipplesStyle};
+
+/// Easing curve of a [`RipplesStyleTransition`].
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize,
+)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starts slow, `t²`.
+    EaseIn,
+    /// Ends slow, `1 - (1 - t)²`.
+    EaseOut,
+    /// Starts and ends slow, smoothstep.
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eases `t`, clamped to [0, 1].
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Fades the ripples of a [`RipplesCamera`] from one style to another.
+///
+/// While the transition runs, the ripples and the jump flood use a blend of
+/// both styles, with colours and gradients blended in linear space. When it
+/// completes, the camera's `Handle<RipplesStyle>` is set to `to` and the
+/// component removed.
+///
+/// Until both styles are loaded, the camera keeps drawing its own style.
+#[derive(Clone, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component)]
+pub struct RipplesStyleTransition {
+    pub from: Handle<RipplesStyle>,
+    pub to: Handle<RipplesStyle>,
+    /// Length of the transition in seconds.
+    pub duration: f32,
+    /// Seconds since the transition started.
+    pub elapsed: f32,
+    pub easing: Easing,
+}
+
+impl RipplesStyleTransition {
+    pub fn new(from: Handle<RipplesStyle>, to: Handle<RipplesStyle>, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Eased progress, from 0 at the start to 1 once `duration` elapsed.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        self.easing.apply(self.elapsed / self.duration)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+type Transitions<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut RipplesStyleTransition, &'static mut Handle<RipplesStyle>),
+>;
+
+pub(crate) fn advance_ripples_style_transitions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transitions: Transitions,
+) {
+    for (entity, mut transition, mut style) in transitions.iter_mut() {
+        transition.elapsed += time.delta_seconds();
+
+        if transition.is_finished() {
+            *style = transition.to.clone();
+            commands.entity(entity).remove::<RipplesStyleTransition>();
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct ExtractedRipplesStyleTransition {
+    from: Handle<RipplesStyle>,
+    to: Handle<RipplesStyle>,
+    progress: f32,
+}
+
+pub(crate) fn extract_ripples_style_transitions(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    transitions: Extract<Query<(Entity, &RipplesStyleTransition), With<RipplesCamera>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(transitions.iter().map(|(entity, transition)| {
+        (
+            entity,
+            (ExtractedRipplesStyleTransition {
+                from: transition.from.clone_weak(),
+                to: transition.to.clone_weak(),
+                progress: transition.progress(),
+            },),
+        )
+    }));
+    *previous_len = batches.len();
+
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// Ripples params of the views with a [`RipplesStyleTransition`], blended
+/// from both styles and used instead of the view's style.
+///
+/// The params of a view are created when its transition starts, and
+/// rewritten in place every frame until it ends.
+#[derive(Default)]
+pub(crate) struct BlendedRipplesParams(HashMap<Entity, GpuRipplesParams>);
+
+impl BlendedRipplesParams {
+    pub(crate) fn get(&self, view: Entity) -> Option<&GpuRipplesParams> {
+        self.0.get(&view)
+    }
+}
+
+pub(crate) fn prepare_ripples_style_transitions(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    water_effect_res: Res<WaterEffectResources>,
+    styles: Res<RenderAssets<RipplesStyle>>,
+    mut blended: ResMut<BlendedRipplesParams>,
+    transitions: Query<(Entity, &ExtractedRipplesStyleTransition)>,
+) {
+    let mut previous = std::mem::take(&mut blended.0);
+
+    for (entity, transition) in transitions.iter() {
+        let (from, to) = match (styles.get(&transition.from), styles.get(&transition.to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+
+        let params = from.params.lerp(&to.params, transition.progress);
+        let gradient = from
+            .gradient
+            .iter()
+            .zip(&to.gradient)
+            .map(|(from, to)| from.lerp(*to, transition.progress));
+        let view_params = match previous.remove(&entity) {
+            Some(mut view_params) => {
+                view_params.update(params, gradient, &device, &queue);
+                view_params
+            }
+            None => GpuRipplesParams::new(params, gradient.collect(), &device, &queue, &water_effect_res),
+        };
+        blended.0.insert(entity, view_params);
+    }
+}
+
+/// Params to draw a view with: the blend of its transition if it has one,
+/// else those of its style.
+pub(crate) fn view_ripples_params<'a>(
+    styles: &'a RenderAssets<RipplesStyle>,
+    blended: &'a BlendedRipplesParams,
+    view: Entity,
+    style: &Handle<RipplesStyle>,
+) -> Option<&'a GpuRipplesParams> {
+    blended.get(view).or_else(|| styles.get(style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_curves_span_zero_to_one() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert_eq!(easing.apply(2.0), 1.0, "{:?}", easing);
+        }
+
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn switches_to_the_target_style_when_finished() {
+        let mut app = App::new();
+        app.add_plugin(bevy::core::CorePlugin)
+            .add_plugin(bevy::time::TimePlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<RipplesStyle>()
+            .add_system(advance_ripples_style_transitions);
+
+        let mut styles = app.world.resource_mut::<Assets<RipplesStyle>>();
+        let from = styles.add(RipplesStyle::default());
+        let to = styles.add(RipplesStyle {
+            speed: 1.0,
+            ..Default::default()
+        });
+
+        let transition = RipplesStyleTransition::new(from.clone(), to.clone(), 1.0);
+        let camera = app.world.spawn().insert(from).insert(transition).id();
+
+        app.update();
+        assert_eq!(app.world.get::<RipplesStyleTransition>(camera).unwrap().elapsed, 0.0);
+
+        app.world
+            .entity_mut(camera)
+            .get_mut::<RipplesStyleTransition>()
+            .unwrap()
+            .elapsed = 1.0;
+        app.update();
+
+        assert!(app.world.get::<RipplesStyleTransition>(camera).is_none());
+        assert_eq!(app.world.get::<Handle<RipplesStyle>>(camera), Some(&to));
+    }
+}
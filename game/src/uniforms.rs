@@ -0,0 +1,66 @@
+//! Uniform structs shared with the shaders.
+//!
+//! encase's `ShaderType` derive emits layout checks that are never called,
+//! which trips `dead_code` on every struct using it, hence the allow below.
+#![allow(dead_code)]
+
+use bevy::{prelude::*, render::render_resource::ShaderType};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ShaderType)]
+pub struct JumpDist {
+    pub dist: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, ShaderType)]
+pub struct Dimensions {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    inv_width: f32,
+    inv_height: f32,
+}
+
+impl Dimensions {
+    pub fn new(width: u32, height: u32) -> Dimensions {
+        Dimensions {
+            width: width as f32,
+            height: height as f32,
+            inv_width: 1.0 / width as f32,
+            inv_height: 1.0 / height as f32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, ShaderType)]
+pub struct RipplesParams {
+    pub(crate) water_color: Vec4,
+    pub(crate) ripples_color: Vec4,
+    pub(crate) distance_from_coast: f32,
+    pub(crate) frequency: f32,
+    pub(crate) speed: f32,
+    pub(crate) foam_color: Vec4,
+    pub(crate) foam_width: f32,
+    pub(crate) foam_noise_scale: f32,
+    pub(crate) foam_noise_speed: f32,
+    pub(crate) wind_direction: Vec2,
+}
+
+/// Most ripple impulses drawn at once, must match `MAX_RIPPLE_IMPULSES` in
+/// `ripples.wgsl`.
+pub const MAX_RIPPLE_IMPULSES: usize = 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, ShaderType)]
+pub struct RippleImpulseUniform {
+    /// Centre in view pixels, y down.
+    pub(crate) position: Vec2,
+    /// Radius in view pixels the outermost ring has spread to.
+    pub(crate) front: f32,
+    /// Strength left after decaying over the lifetime.
+    pub(crate) amplitude: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, ShaderType)]
+pub struct RippleImpulses {
+    // The array comes first, uniform arrays must be 16 byte aligned.
+    pub(crate) impulses: [RippleImpulseUniform; MAX_RIPPLE_IMPULSES],
+    pub(crate) count: u32,
+}
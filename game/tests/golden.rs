@@ -0,0 +1,400 @@
+//! Golden image tests of the water effect render graph.
+//!
+//! Each test renders a scene headless on the software adapter and compares
+//! the ripples, mask and jump flood outputs against the PNGs in
+//! `tests/golden`. Run with `BLESS_GOLDEN=1` to write new goldens, or rewrite
+//! them after an intended change; otherwise a missing golden fails the test,
+//! and so does a missing software adapter.
+//!
+//! Needs the `headless` feature: `cargo test -p game --features headless`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use game::*;
+
+/// Largest difference of a channel, out of 255, for pixels to match.
+const CHANNEL_TOLERANCE: u8 = 3;
+/// Fraction of pixels allowed to differ by more than `CHANNEL_TOLERANCE`.
+const MAX_MISMATCHED_FRACTION: f32 = 0.005;
+
+/// Settings the tests start from, with the jump flood in `Rg32Float`:
+/// software adapters rarely support the default `Rg16Snorm`.
+fn settings() -> WaterEffectSettings {
+    WaterEffectSettings {
+        jfa_texture_format: TextureFormat::Rg32Float,
+        ..Default::default()
+    }
+}
+
+/// A headless run drawing `style` with `settings`, which panics when there is
+/// no software adapter that can render them.
+fn headless(style: RipplesStyle, settings: WaterEffectSettings) -> HeadlessWaterEffect {
+    let headless = HeadlessWaterEffect {
+        settings,
+        style,
+        size: UVec2::new(320, 240),
+        asset_folder: concat!(env!("CARGO_MANIFEST_DIR"), "/../assets").into(),
+        ..Default::default()
+    };
+
+    if let Err(err) = headless.software_adapter() {
+        panic!("cannot render the golden images: {}", err);
+    }
+    headless
+}
+
+/// Where `panned_coastline` moves the scene and the camera to.
+const PANNED_POSITION: Vec2 = Vec2::new(1500.0, -700.0);
+/// How much `panned_coastline` scales the scene and zooms the camera out.
+const PANNED_SCALE: f32 = 2.0;
+
+/// A bay carved out of the land by overlapping water sprites, as in the
+/// `static_coastline` example.
+fn static_coastline(commands: Commands) {
+    spawn_coastline(commands, Vec2::ZERO, 1.0);
+}
+
+/// `static_coastline`, scaled by `PANNED_SCALE` around `PANNED_POSITION`.
+fn panned_coastline(commands: Commands) {
+    spawn_coastline(commands, PANNED_POSITION, PANNED_SCALE);
+}
+
+fn spawn_coastline(mut commands: Commands, position: Vec2, scale: f32) {
+    let water_sprites = [
+        (Vec2::new(320., 70.), Vec3::new(0.0, -85.0, 0.0), 0.0),
+        (Vec2::new(110., 90.), Vec3::new(-30.0, -10.0, 1.0), 0.3),
+        (Vec2::new(65., 50.), Vec3::new(15.0, 30.0, 2.0), -0.2),
+    ];
+
+    for (size, translation, angle) in water_sprites {
+        let translation = (translation.truncate() * scale + position).extend(translation.z);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE,
+                    custom_size: Some(size * scale),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation)
+                    .with_rotation(Quat::from_rotation_z(angle)),
+                ..Default::default()
+            })
+            .insert(WaterSource::default());
+    }
+}
+
+fn assert_golden(name: &str, image: &CapturedImage) {
+    let actual = image.to_rgba8().unwrap();
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+
+    if env::var_os("BLESS_GOLDEN").is_some() {
+        save(&golden_path, image.size, &actual);
+        return;
+    }
+    assert!(
+        golden_path.exists(),
+        "{} is missing, run with BLESS_GOLDEN=1 to write it",
+        golden_path.display()
+    );
+
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|err| panic!("could not read {}: {}", golden_path.display(), err))
+        .into_rgba8();
+    assert_eq!(
+        UVec2::from(golden.dimensions()),
+        image.size,
+        "{}: size differs from the golden",
+        name
+    );
+
+    let mismatched = golden
+        .as_raw()
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .filter(|(expected, actual)| {
+            expected
+                .iter()
+                .zip(actual.iter())
+                .any(|(e, a)| e.abs_diff(*a) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let mismatched_fraction = mismatched as f32 / (image.size.x * image.size.y) as f32;
+
+    if mismatched_fraction > MAX_MISMATCHED_FRACTION {
+        let actual_path = actual_path(name);
+        save(&actual_path, image.size, &actual);
+        panic!(
+            "{}: {:.2}% of pixels differ from {}, see {}",
+            name,
+            mismatched_fraction * 100.0,
+            golden_path.display(),
+            actual_path.display()
+        );
+    }
+}
+
+fn actual_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.actual.png", name))
+}
+
+fn save(path: &Path, size: UVec2, rgba: &[u8]) {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).unwrap();
+    }
+    image::save_buffer(path, rgba, size.x, size.y, image::ColorType::Rgba8)
+        .unwrap_or_else(|err| panic!("could not write {}: {}", path.display(), err));
+}
+
+fn assert_capture_golden(name: &str, capture: &WaterEffectCapture) {
+    assert_golden(&format!("{}_ripples", name), &capture.ripples);
+    assert_golden(&format!("{}_mask", name), &capture.mask);
+    assert_golden(&format!("{}_jfa", name), &capture.jfa);
+}
+
+#[test]
+fn static_coastline_render_pass() {
+    let headless = headless(RipplesStyle::default(), settings());
+
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_capture_golden("static_coastline", &capture);
+    assert_seeds_follow_mask(&capture);
+    assert_jfa_matches_reference(&capture, &RipplesStyle::default());
+}
+
+#[test]
+fn land_from_sprites_inverts_the_mask() {
+    let water = headless(RipplesStyle::default(), settings());
+    let land = headless(
+        RipplesStyle::default(),
+        WaterEffectSettings {
+            mask_mode: MaskMode::LandFromSprites,
+            ..settings()
+        },
+    );
+
+    // The same sprites, read as the coastline instead of the water.
+    let water = water.run(static_coastline).expect("headless run failed");
+    let land = land.run(static_coastline).expect("headless run failed");
+    assert_golden("land_coastline_mask", &land.mask);
+    assert_seeds_follow_mask(&land);
+
+    for (water, land) in water.mask.data.iter().zip(land.mask.data.iter()) {
+        assert!(
+            water.abs_diff(255 - land) <= CHANNEL_TOLERANCE,
+            "land coverage {} is not the inverse of {}",
+            land,
+            water
+        );
+    }
+}
+
+/// Checks that the jump flood is seeded by the fully covered pixels of the
+/// mask, and that the pixels it reached from there are closest to covered
+/// ones.
+fn assert_seeds_follow_mask(capture: &WaterEffectCapture) {
+    let (mask, jfa) = (&capture.mask, &capture.jfa);
+    assert_eq!(jfa.format, TextureFormat::Rg32Float);
+    assert_eq!(jfa.size, mask.size);
+    assert!(mask.data.contains(&255), "nothing is covered by the mask");
+
+    let size = mask.size.as_vec2();
+    let coverage_at = |pixel: UVec2| mask.data[(pixel.y * mask.size.x + pixel.x) as usize];
+    for (index, position) in jfa.data.chunks_exact(8).enumerate() {
+        let pixel = UVec2::new(index as u32 % mask.size.x, index as u32 / mask.size.x);
+        let position = Vec2::new(
+            f32::from_le_bytes(position[..4].try_into().unwrap()),
+            f32::from_le_bytes(position[4..].try_into().unwrap()),
+        );
+
+        if coverage_at(pixel) == 255 {
+            let offset = position * size - (pixel.as_vec2() + 0.5);
+            assert!(offset.length() < 0.1, "{} is not its own seed: {}", pixel, position);
+        } else if position.x >= 0.0 {
+            let seed = (position * size).as_uvec2().min(mask.size - 1);
+            assert!(coverage_at(seed) > 0, "{} is closest to {}, which is not covered", pixel, seed);
+        }
+    }
+}
+
+/// Largest difference, in pixels, between the distances to the coast of the
+/// GPU jump flood and of `JfaReference` for a pixel to match.
+const DISTANCE_TOLERANCE: f32 = 0.5;
+
+/// Checks the jump flood against `JfaReference` run on the captured mask,
+/// over the distance from the coast of `style`.
+fn assert_jfa_matches_reference(capture: &WaterEffectCapture, style: &RipplesStyle) {
+    let (mask, jfa) = (&capture.mask, &capture.jfa);
+    let coverage: Vec<f32> = mask.data.iter().map(|&value| value as f32 / 255.0).collect();
+    let reference = JfaReference::run(&coverage, mask.size.x, mask.size.y, style.distance_from_coast);
+
+    let size = jfa.size.as_vec2();
+    let mut mismatched = 0;
+    for (index, position) in jfa.data.chunks_exact(8).enumerate() {
+        let pixel = UVec2::new(index as u32 % jfa.size.x, index as u32 / jfa.size.x);
+        let position = Vec2::new(
+            f32::from_le_bytes(position[..4].try_into().unwrap()),
+            f32::from_le_bytes(position[4..].try_into().unwrap()),
+        );
+        let distance = (position.x >= 0.0).then(|| (position * size).distance(pixel.as_vec2() + 0.5));
+
+        let matches = match (distance, reference.distance(pixel.x, pixel.y)) {
+            (Some(distance), Some(expected)) => (distance - expected).abs() <= DISTANCE_TOLERANCE,
+            (None, None) => true,
+            _ => false,
+        };
+        if !matches {
+            mismatched += 1;
+        }
+    }
+
+    let mismatched_fraction = mismatched as f32 / (jfa.size.x * jfa.size.y) as f32;
+    assert!(
+        mismatched_fraction <= MAX_MISMATCHED_FRACTION,
+        "{:.2}% of the jump flood differs from the CPU reference",
+        mismatched_fraction * 100.0
+    );
+}
+
+#[test]
+fn static_coastline_compute() {
+    // Fails when the software adapter has no Rg32Float storage textures,
+    // which the compute backend cannot do without.
+    let headless = headless(
+        RipplesStyle::default(),
+        WaterEffectSettings {
+            jfa_backend: JfaBackend::Compute,
+            ..Default::default()
+        },
+    );
+
+    // Both backends store Rg32Float positions and share the goldens.
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_capture_golden("static_coastline", &capture);
+    assert_seeds_follow_mask(&capture);
+    assert_jfa_matches_reference(&capture, &RipplesStyle::default());
+}
+
+#[test]
+fn gradient_colours_the_water() {
+    let style = RipplesStyle {
+        gradient: vec![
+            GradientStop {
+                position: 0.0,
+                color: Color::rgb(0.3, 0.9, 0.7),
+            },
+            GradientStop {
+                position: 1.0,
+                color: Color::rgb(0.0, 0.1, 0.4),
+            },
+        ],
+        ..Default::default()
+    };
+    let headless = headless(style, settings());
+
+    // Only the ripples depend on the style.
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_golden("gradient_coastline_ripples", &capture.ripples);
+}
+
+#[test]
+fn foam_lines_the_coast() {
+    let style = RipplesStyle {
+        foam_width: 6.0,
+        ..Default::default()
+    };
+    let headless = headless(style, settings());
+
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_golden("foam_coastline_ripples", &capture.ripples);
+}
+
+#[test]
+fn wind_calms_sheltered_coasts() {
+    let style = RipplesStyle {
+        // Onto the coast below the bay, away from the one above.
+        wind_direction: Vec2::new(0.0, -0.8),
+        ..Default::default()
+    };
+    let headless = headless(
+        style,
+        WaterEffectSettings {
+            coast_direction_output: true,
+            ..settings()
+        },
+    );
+
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_golden("wind_coastline_ripples", &capture.ripples);
+    let coast_direction = capture
+        .coast_direction
+        .expect("the coast direction was not captured");
+    assert_golden("static_coastline_coast_direction", &coast_direction);
+}
+
+#[test]
+fn distance_field_is_signed_by_the_mask() {
+    let headless = headless(
+        RipplesStyle::default(),
+        WaterEffectSettings {
+            distance_field_output: true,
+            ..settings()
+        },
+    );
+
+    // The second jump flood leaves the other outputs untouched.
+    let capture = headless.run(static_coastline).expect("headless run failed");
+    assert_capture_golden("static_coastline", &capture);
+
+    let distance_field = capture
+        .distance_field
+        .expect("the distance field was not captured");
+    assert_golden("static_coastline_distance_field", &distance_field);
+
+    // Grey levels above 128 are over water, below over land.
+    let mask = capture.mask.to_rgba8().unwrap();
+    let distances = distance_field.to_rgba8().unwrap();
+    for (mask, distance) in mask.chunks_exact(4).zip(distances.chunks_exact(4)) {
+        match mask[0] {
+            0 => assert!(distance[0] > 128, "water at a distance of {}", distance[0]),
+            255 => assert!(distance[0] < 128, "land at a distance of {}", distance[0]),
+            _ => {}
+        }
+    }
+}
+
+/// `static_coastline` with a splash in the bay and one in the open water
+/// below, whose rings run into the land.
+fn splashed_coastline(commands: Commands, mut impulses: EventWriter<RippleImpulse>) {
+    spawn_coastline(commands, Vec2::ZERO, 1.0);
+    // Half way through their lifetime when captured, after three frames.
+    impulses.send(RippleImpulse::new(Vec2::new(-30.0, -10.0), 1.0, 60.0, 0.1));
+    impulses.send(RippleImpulse::new(Vec2::new(90.0, -90.0), 1.0, 100.0, 0.1));
+}
+
+#[test]
+fn impulses_ring_the_water() {
+    let headless = headless(RipplesStyle::default(), settings());
+
+    let capture = headless.run(splashed_coastline).expect("headless run failed");
+    assert_golden("splashed_coastline_ripples", &capture.ripples);
+}
+
+#[test]
+fn panned_camera_follows_main_camera() {
+    let headless = HeadlessWaterEffect {
+        camera_position: PANNED_POSITION,
+        camera_scale: PANNED_SCALE,
+        ..headless(RipplesStyle::default(), settings())
+    };
+
+    // The water cameras follow the main camera, so the view is the same as
+    // the unmoved scene.
+    let capture = headless.run(panned_coastline).expect("headless run failed");
+    assert_capture_golden("static_coastline", &capture);
+}
@@ -0,0 +1,138 @@
+//! Scene round trip of a water effect rig.
+//!
+//! Spawns a rig, serializes the world to a RON scene and loads it into an
+//! empty world, checking that the reflected components survive, that the
+//! rig points at the loaded entities and that it gets images to render into.
+
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistryArc;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_resource::Extent3d;
+use bevy::scene::serde::SceneDeserializer;
+use serde::de::DeserializeSeed;
+use game::*;
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugin(bevy::core::CorePlugin)
+        .add_plugin(bevy::time::TimePlugin)
+        .add_plugin(bevy::asset::AssetPlugin)
+        .add_plugin(bevy::transform::TransformPlugin)
+        .add_plugin(bevy::hierarchy::HierarchyPlugin)
+        .init_resource::<Windows>()
+        .add_asset::<Image>()
+        .add_asset::<Shader>()
+        .add_plugin(WaterEffectPlugin::default());
+    app
+}
+
+fn spawn_rig(world: &mut World, style: Handle<RipplesStyle>) -> WaterEffectRig {
+    let mut queue = CommandQueue::default();
+    let rig = Commands::new(&mut queue, world).spawn_water_effect(style);
+    queue.apply(world);
+    world.spawn().insert(WaterSource { visible_in_main_view: true });
+    rig
+}
+
+#[test]
+fn water_rig_round_trips_through_a_scene() {
+    let mut source = app();
+    let style = source
+        .world
+        .resource_mut::<Assets<RipplesStyle>>()
+        .add(RipplesStyle::default());
+    spawn_rig(&mut source.world, style.clone());
+
+    let type_registry = source.world.resource::<TypeRegistryArc>().clone();
+    let ron = DynamicScene::from_world(&source.world, &type_registry)
+        .serialize_ron(&type_registry)
+        .unwrap();
+
+    let mut deserializer = ron::de::Deserializer::from_str(&ron).unwrap();
+    let scene = SceneDeserializer {
+        type_registry: &type_registry.read(),
+    }
+    .deserialize(&mut deserializer)
+    .unwrap();
+
+    let mut target = app();
+    // Offset the entities, so that a rig left unmapped points nowhere.
+    target.world.spawn();
+    let mut entity_map = Default::default();
+    scene.write_to_world(&mut target.world, &mut entity_map).unwrap();
+    let world = &mut target.world;
+
+    let rig = *world.query::<&WaterEffectRig>().single(world);
+    assert_eq!(world.get::<WaterEffectRig>(rig.root), Some(&rig));
+    assert!(world.get::<MainCamera>(rig.main_camera).is_some());
+    assert!(world.get::<WaterSpritesCamera>(rig.water_sprites_camera).is_some());
+    assert!(world.get::<RipplesCamera>(rig.ripples_camera).is_some());
+    assert!(world.get::<RipplesTexture>(rig.ripples_texture).is_some());
+
+    let children = world.get::<Children>(rig.root).unwrap();
+    assert_eq!(
+        &children[..],
+        &[
+            rig.main_camera,
+            rig.water_sprites_camera,
+            rig.ripples_camera,
+            rig.ripples_texture
+        ]
+    );
+
+    let loaded_style = world.get::<Handle<RipplesStyle>>(rig.ripples_camera).unwrap();
+    assert_eq!(loaded_style.id, style.id);
+
+    let sources: Vec<_> = world.query::<&WaterSource>().iter(world).copied().collect();
+    assert_eq!(sources, [WaterSource { visible_in_main_view: true }]);
+
+    // The images are not part of the scene, the loaded rig gets its own.
+    assert!(world.get::<WaterEffectImages>(rig.ripples_camera).is_none());
+    let size = Extent3d {
+        width: 320,
+        height: 240,
+        depth_or_array_layers: 1,
+    };
+    let mut main_target = Image::default();
+    main_target.resize(size);
+    let main_target = world.resource_mut::<Assets<Image>>().add(main_target);
+    // Cameras and sprites don't go through scenes in this version of Bevy,
+    // their `Option<Viewport>` and `Option<Vec2>` can't be serialized.
+    world.entity_mut(rig.main_camera).insert(Camera {
+        target: RenderTarget::Image(main_target),
+        ..default()
+    });
+    for camera in [rig.water_sprites_camera, rig.ripples_camera] {
+        world.entity_mut(camera).insert(Camera::default());
+    }
+    world.entity_mut(rig.ripples_texture).insert(Sprite::default());
+    target.update();
+    let world = &target.world;
+
+    let water_effect_images = world.get::<WaterEffectImages>(rig.ripples_camera).unwrap();
+    let images = world.resource::<Assets<Image>>();
+    for handle in [
+        &water_effect_images.rendered_water_sprites,
+        &water_effect_images.rendered_ripples,
+    ] {
+        assert_eq!(images.get(handle).unwrap().texture_descriptor.size, size);
+    }
+    let target = |entity| world.get::<Camera>(entity).unwrap().target.clone();
+    assert_eq!(
+        target(rig.water_sprites_camera),
+        RenderTarget::Image(water_effect_images.rendered_water_sprites.clone())
+    );
+    assert_eq!(
+        target(rig.ripples_camera),
+        RenderTarget::Image(water_effect_images.rendered_ripples.clone())
+    );
+    assert_eq!(
+        world.get::<Handle<Image>>(rig.ripples_texture),
+        Some(&water_effect_images.rendered_ripples)
+    );
+    assert_eq!(
+        world.get::<Sprite>(rig.ripples_texture).unwrap().custom_size,
+        Some(Vec2::new(320.0, 240.0))
+    );
+}
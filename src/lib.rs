@@ -0,0 +1,8 @@
+//! 2D water ripples for Bevy, rendered with a jump flood distance field.
+//!
+//! Add [`WaterEffectPlugin`] to the app, spawn the cameras with
+//! [`spawn_water_effect`](WaterEffectCommandsExt::spawn_water_effect) and mark
+//! the sprites that make up the water with [`WaterSource`]. The `examples/`
+//! directory contains complete scenes.
+
+pub use game::*;
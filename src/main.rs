@@ -1,10 +0,0 @@
-use bevy::prelude::*;
-
-use game::GamePlugin;
-
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(GamePlugin)
-        .run();
-}
\ No newline at end of file